@@ -1,16 +1,664 @@
-use std::str::FromStr;
+use std::{
+    fs,
+    io::{self, Write},
+    os::unix::fs::{OpenOptionsExt, PermissionsExt},
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
-use anyhow::Result;
-use subxt_signer::{sr25519::Keypair, SecretUri};
+use anyhow::{anyhow, Result};
+use hmac::Hmac;
+use log::{info, warn};
+use schnorrkel::{
+    derive::{ChainCode, Derivation},
+    ExpansionMode, MiniSecretKey,
+};
+use serde::{Deserialize, Serialize};
+use sha2::Sha512;
+use subxt::{
+    ext::codec::Encode,
+    tx::Signer,
+    utils::{AccountId32, MultiAddress, MultiSignature},
+    SubstrateConfig,
+};
+use subxt_signer::{bip39::Mnemonic, ecdsa, DeriveJunction, ExposeSecret, SecretUri};
 
-pub fn generate_bench_key_pairs(prefix: &str, num: u32) -> Result<Vec<Keypair>> {
-    let mut keys = Vec::new();
+use crate::{
+    account_list,
+    workload::{decode_hex, encode_hex},
+};
 
-    for i in 0..num {
-        let uri = SecretUri::from_str(&format!("//bench-{}:{}", prefix, i))?;
-        let key = Keypair::from_uri(&uri)?;
-        keys.push(key);
+const DEFAULT_PATH: &str = "//bench-{prefix}:{index}";
+// prefixes whose cache keeps the secrets, the accounts signing every run. receivers only
+// sign when reclaimed, their cache holds addresses only
+const SECRET_CACHE_PREFIXES: [&str; 1] = ["sender"];
+
+/// Signature scheme of the bench accounts.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyScheme {
+    #[default]
+    Sr25519,
+    Ed25519,
+    Ecdsa,
+}
+
+/// The `[derivation]` table of the config file, how the bench accounts are derived.
+#[derive(Debug, Clone, Deserialize)]
+pub struct KeyConfig {
+    #[serde(default)]
+    pub scheme: KeyScheme,
+    // mnemonic or 0x prefixed 32 byte hex seed, the well known dev phrase when empty
+    #[serde(default)]
+    pub seed: String,
+    // mnemonic password, ignored for hex seeds
+    #[serde(default)]
+    pub password: Option<String>,
+    // path of the i-th account, {prefix} and {index} are replaced
+    #[serde(default = "default_path")]
+    pub path: String,
+    // directory of the derived key files, no caching when unset
+    #[serde(default)]
+    pub cache_dir: Option<String>,
+    // take the accounts from this .json/.csv list instead of deriving them
+    #[serde(default)]
+    pub import: Option<String>,
+}
+
+fn default_path() -> String {
+    DEFAULT_PATH.to_string()
+}
+
+impl Default for KeyConfig {
+    fn default() -> Self {
+        Self {
+            scheme: KeyScheme::default(),
+            seed: String::new(),
+            password: None,
+            path: default_path(),
+            cache_dir: None,
+            import: None,
+        }
+    }
+}
+
+// One cache file per seed and prefix.
+#[derive(Serialize, Deserialize)]
+struct KeyCache {
+    scheme: KeyScheme,
+    accounts: Vec<CachedKey>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedKey {
+    account_id: String,
+    // hex, missing for accounts that were only needed as receivers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    secret: Option<String>,
+}
+
+impl KeyConfig {
+    /// Whether the accounts derive from the public dev phrase, so anyone can sign for them.
+    pub fn uses_dev_phrase(&self) -> bool {
+        self.import.is_none() && self.seed.trim().is_empty()
+    }
+
+    /// Key pairs of the `num` bench accounts named `prefix`, e.g. "sender".
+    pub fn derive(&self, prefix: &str, num: u32) -> Result<Vec<AccountKeypair>> {
+        if let Some(path) = &self.import {
+            return account_list::load_keys(path, self.scheme, prefix, num);
+        }
+        if let Some(cache) = self.read_cache(prefix, num) {
+            let secrets = cache
+                .accounts
+                .iter()
+                .take(num as usize)
+                .map(|key| key.secret.as_deref())
+                .collect::<Option<Vec<_>>>();
+            if let Some(secrets) = secrets {
+                return secrets
+                    .into_iter()
+                    .map(|secret| AccountKeypair::from_secret(self.scheme, &decode_hex(secret)?))
+                    .collect();
+            }
+        }
+        let keys = self.derive_parallel(prefix, num)?;
+        self.write_cache(prefix, &keys, SECRET_CACHE_PREFIXES.contains(&prefix));
+        Ok(keys)
+    }
+
+    /// Account ids of the `num` bench accounts named `prefix`, for accounts that never
+    /// sign. A cache hit needs no derivation at all, and only the ids are kept.
+    pub fn derive_account_ids(&self, prefix: &str, num: u32) -> Result<Vec<AccountId32>> {
+        if let Some(path) = &self.import {
+            return account_list::load_account_ids(path, prefix, num);
+        }
+        if let Some(cache) = self.read_cache(prefix, num) {
+            return cache
+                .accounts
+                .iter()
+                .take(num as usize)
+                .map(|key| {
+                    AccountId32::from_str(&key.account_id)
+                        .map_err(|e| anyhow!("invalid cached account {}: {:?}", key.account_id, e))
+                })
+                .collect();
+        }
+        let keys = self.derive_parallel(prefix, num)?;
+        self.write_cache(prefix, &keys, false);
+        Ok(keys.iter().map(|k| k.account_id()).collect())
+    }
+
+    // The root key is derived once, each thread then only walks the path junctions.
+    fn derive_parallel(&self, prefix: &str, num: u32) -> Result<Vec<AccountKeypair>> {
+        let mut root = self.seed.clone();
+        if let Some(password) = &self.password {
+            root.push_str("///");
+            root.push_str(password);
+        }
+        let root = AccountKeypair::from_uri(self.scheme, &SecretUri::from_str(&root)?)?;
+
+        let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+        let indexes = (0..num).collect::<Vec<_>>();
+        let per_thread = indexes.len().div_ceil(threads).max(1);
+        std::thread::scope(|scope| {
+            let handles = indexes
+                .chunks(per_thread)
+                .map(|chunk| {
+                    let root = &root;
+                    scope.spawn(move || {
+                        chunk
+                            .iter()
+                            .map(|i| root.derive(&self.junctions(prefix, *i)?))
+                            .collect::<Result<Vec<_>>>()
+                    })
+                })
+                .collect::<Vec<_>>();
+            let mut keys = Vec::with_capacity(indexes.len());
+            for handle in handles {
+                keys.extend(handle.join().expect("derivation thread panicked")?);
+            }
+            Ok(keys)
+        })
+    }
+
+    /// The secret uri of one account, what `derive` would derive it from.
+    pub fn secret_uri(&self, prefix: &str, index: u32) -> String {
+        let mut uri = format!("{}{}", self.seed, self.account_path(prefix, index));
+        if let Some(password) = &self.password {
+            uri.push_str("///");
+            uri.push_str(password);
+        }
+        uri
+    }
+
+    fn account_path(&self, prefix: &str, index: u32) -> String {
+        self.path
+            .replace("{prefix}", prefix)
+            .replace("{index}", &index.to_string())
+    }
+
+    fn junctions(&self, prefix: &str, index: u32) -> Result<Vec<DeriveJunction>> {
+        let path = self.account_path(prefix, index);
+        let uri = SecretUri::from_str(&path)
+            .map_err(|e| anyhow!("invalid derivation path {}: {}", path, e))?;
+        Ok(uri.junctions)
+    }
+
+    // Named after a hash of everything the keys depend on, so the seed is not in the name.
+    fn cache_path(&self, prefix: &str) -> Option<PathBuf> {
+        let dir = self.cache_dir.as_ref()?;
+        let hash = (
+            self.scheme as u8,
+            &self.seed,
+            self.password.as_deref().unwrap_or(""),
+            &self.path,
+            prefix,
+        )
+            .using_encoded(sp_core_hashing::blake2_256);
+        Some(PathBuf::from(dir).join(format!("{}-{}.json", prefix, encode_hex(&hash[..16]))))
+    }
+
+    fn read_cache(&self, prefix: &str, num: u32) -> Option<KeyCache> {
+        let path = self.cache_path(prefix)?;
+        let cache: KeyCache = match fs::read(&path).map(|bytes| serde_json::from_slice(&bytes)) {
+            Ok(Ok(cache)) => cache,
+            Ok(Err(e)) => {
+                warn!("ignore unreadable key cache {}: {}", path.display(), e);
+                return None;
+            }
+            Err(_) => return None,
+        };
+        (cache.scheme == self.scheme && cache.accounts.len() >= num as usize).then_some(cache)
+    }
+
+    // A failed write only costs the next start another derivation.
+    fn write_cache(&self, prefix: &str, keys: &[AccountKeypair], with_secrets: bool) {
+        let Some(path) = self.cache_path(prefix) else {
+            return;
+        };
+        let cache = KeyCache {
+            scheme: self.scheme,
+            accounts: keys
+                .iter()
+                .map(|key| CachedKey {
+                    account_id: key.account_id().to_string(),
+                    secret: with_secrets.then(|| encode_hex(&key.secret())),
+                })
+                .collect(),
+        };
+        let res = path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .map_err(anyhow::Error::from)
+            .and_then(|_| Ok(serde_json::to_vec(&cache)?))
+            .and_then(|bytes| Ok(write_private(&path, &bytes)?));
+        match res {
+            Ok(()) => info!(
+                "cached {} {} keys in {}",
+                keys.len(),
+                prefix,
+                path.display()
+            ),
+            Err(e) => warn!("can not write key cache {}: {}", path.display(), e),
+        }
+    }
+}
+
+/// A key pair of any of the supported schemes, signing as a `MultiSignature`.
+#[derive(Clone)]
+pub enum AccountKeypair {
+    Sr25519(schnorrkel::Keypair),
+    Ed25519(ed25519_zebra::SigningKey),
+    Ecdsa(ecdsa::Keypair),
+}
+
+impl AccountKeypair {
+    pub fn from_uri(scheme: KeyScheme, uri: &SecretUri) -> Result<Self> {
+        let key = match scheme {
+            KeyScheme::Sr25519 => {
+                let mini = MiniSecretKey::from_bytes(&seed_from_uri(uri)?)
+                    .map_err(|e| anyhow!("invalid sr25519 seed: {}", e))?;
+                AccountKeypair::Sr25519(mini.expand_to_keypair(ExpansionMode::Ed25519))
+                    .derive(&uri.junctions)?
+            }
+            KeyScheme::Ed25519 => {
+                AccountKeypair::Ed25519(ed25519_zebra::SigningKey::from(seed_from_uri(uri)?))
+                    .derive(&uri.junctions)?
+            }
+            KeyScheme::Ecdsa => AccountKeypair::Ecdsa(ecdsa::Keypair::from_uri(uri)?),
+        };
+        Ok(key)
+    }
+
+    /// The inverse of `secret`.
+    pub fn from_secret(scheme: KeyScheme, secret: &[u8]) -> Result<Self> {
+        let key = match scheme {
+            KeyScheme::Sr25519 => AccountKeypair::Sr25519(
+                schnorrkel::SecretKey::from_bytes(secret)
+                    .map_err(|e| anyhow!("invalid sr25519 secret: {}", e))?
+                    .to_keypair(),
+            ),
+            KeyScheme::Ed25519 => AccountKeypair::Ed25519(
+                ed25519_zebra::SigningKey::try_from(secret)
+                    .map_err(|e| anyhow!("invalid ed25519 secret: {}", e))?,
+            ),
+            KeyScheme::Ecdsa => AccountKeypair::Ecdsa(ecdsa::Keypair::from_seed(
+                secret
+                    .try_into()
+                    .map_err(|_| anyhow!("an ecdsa secret has 32 bytes"))?,
+            )?),
+        };
+        Ok(key)
+    }
+
+    /// Secret key bytes, what the key cache stores.
+    pub fn secret(&self) -> Vec<u8> {
+        match self {
+            AccountKeypair::Sr25519(key) => key.secret.to_bytes().to_vec(),
+            AccountKeypair::Ed25519(key) => <[u8; 32]>::from(*key).to_vec(),
+            AccountKeypair::Ecdsa(key) => key.0.secret_key().secret_bytes().to_vec(),
+        }
+    }
+
+    /// A child key of the same scheme. ed25519 and ecdsa only know hard junctions.
+    pub fn derive(&self, junctions: &[DeriveJunction]) -> Result<Self> {
+        let key = match self {
+            // as subxt_signer::sr25519::Keypair::derive
+            AccountKeypair::Sr25519(key) => {
+                let secret = junctions
+                    .iter()
+                    .fold(key.secret.clone(), |secret, junction| match junction {
+                        DeriveJunction::Soft(cc) => secret.derived_key_simple(ChainCode(*cc), []).0,
+                        DeriveJunction::Hard(cc) => secret
+                            .hard_derive_mini_secret_key(Some(ChainCode(*cc)), b"")
+                            .0
+                            .expand(ExpansionMode::Ed25519),
+                    });
+                AccountKeypair::Sr25519(secret.into())
+            }
+            AccountKeypair::Ed25519(key) => {
+                let mut seed = <[u8; 32]>::from(*key);
+                for junction in junctions {
+                    match junction {
+                        DeriveJunction::Soft(_) => {
+                            return Err(anyhow!("ed25519 has no soft derivation"))
+                        }
+                        DeriveJunction::Hard(cc) => {
+                            seed =
+                                ("Ed25519HDKD", seed, cc).using_encoded(sp_core_hashing::blake2_256)
+                        }
+                    }
+                }
+                AccountKeypair::Ed25519(ed25519_zebra::SigningKey::from(seed))
+            }
+            AccountKeypair::Ecdsa(key) => {
+                AccountKeypair::Ecdsa(key.derive(junctions.iter().copied())?)
+            }
+        };
+        Ok(key)
+    }
+
+    /// Public key bytes, 33 compressed bytes for ecdsa and 32 otherwise.
+    pub fn public_key(&self) -> Vec<u8> {
+        match self {
+            AccountKeypair::Sr25519(key) => key.public.to_bytes().to_vec(),
+            AccountKeypair::Ed25519(key) => {
+                <[u8; 32]>::from(ed25519_zebra::VerificationKeyBytes::from(key)).to_vec()
+            }
+            AccountKeypair::Ecdsa(key) => key.public_key().0.to_vec(),
+        }
+    }
+
+    /// The account id, the public key itself except for ecdsa where it is its blake2 hash.
+    pub fn account_id(&self) -> AccountId32 {
+        match self {
+            AccountKeypair::Sr25519(key) => AccountId32(key.public.to_bytes()),
+            AccountKeypair::Ed25519(key) => {
+                AccountId32(ed25519_zebra::VerificationKeyBytes::from(key).into())
+            }
+            AccountKeypair::Ecdsa(key) => key.public_key().to_account_id(),
+        }
+    }
+}
+
+impl Signer<SubstrateConfig> for AccountKeypair {
+    fn account_id(&self) -> AccountId32 {
+        AccountKeypair::account_id(self)
     }
 
-    Ok(keys)
+    fn address(&self) -> MultiAddress<AccountId32, u32> {
+        MultiAddress::Id(AccountKeypair::account_id(self))
+    }
+
+    fn sign(&self, signer_payload: &[u8]) -> MultiSignature {
+        match self {
+            AccountKeypair::Sr25519(key) => {
+                let context = schnorrkel::signing_context(b"substrate");
+                MultiSignature::Sr25519(key.sign(context.bytes(signer_payload)).to_bytes())
+            }
+            AccountKeypair::Ed25519(key) => {
+                MultiSignature::Ed25519(key.sign(signer_payload).into())
+            }
+            AccountKeypair::Ecdsa(key) => MultiSignature::Ecdsa(key.sign(signer_payload).0),
+        }
+    }
+}
+
+// The 32 byte seed sr25519 and ed25519 keys start from, as sp_core::Pair::from_string:
+// a hex seed as is, or the mnemonic entropy stretched with the password.
+/// Write a file only the owner can read, it may hold secret keys. The mode is set again
+/// for a file left by an older version.
+pub fn write_private(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)?;
+    file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    file.write_all(bytes)
+}
+
+fn seed_from_uri(uri: &SecretUri) -> Result<[u8; 32]> {
+    let phrase = uri.phrase.expose_secret();
+    if phrase.starts_with("0x") {
+        return decode_hex(phrase)?
+            .try_into()
+            .map_err(|_| anyhow!("a hex seed has 32 bytes"));
+    }
+    let mnemonic = Mnemonic::parse(phrase.as_str())?;
+    let password = uri.password.as_ref().map(|p| p.expose_secret().as_str());
+    let salt = format!("mnemonic{}", password.unwrap_or(""));
+    let mut big_seed = [0u8; 64];
+    pbkdf2::pbkdf2::<Hmac<Sha512>>(&mnemonic.to_entropy(), salt.as_bytes(), 2048, &mut big_seed)
+        .map_err(|e| anyhow!("invalid mnemonic seed: {}", e))?;
+    Ok(big_seed[..32].try_into().expect("32 of 64 bytes"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEV_PHRASE: &str =
+        "bottom drive obey lake curtain smoke basket hold race lonely fit walk";
+
+    fn key(scheme: KeyScheme, uri: &str) -> AccountKeypair {
+        AccountKeypair::from_uri(scheme, &SecretUri::from_str(uri).unwrap()).unwrap()
+    }
+
+    fn public_hex(scheme: KeyScheme, uri: &str) -> String {
+        encode_hex(&key(scheme, uri).public_key())
+    }
+
+    fn sr25519_reference(uri: &str) -> String {
+        let uri = SecretUri::from_str(uri).unwrap();
+        let key = subxt_signer::sr25519::Keypair::from_uri(&uri).unwrap();
+        encode_hex(&key.public_key().0)
+    }
+
+    fn ecdsa_reference(uri: &str) -> String {
+        let uri = SecretUri::from_str(uri).unwrap();
+        encode_hex(&ecdsa::Keypair::from_uri(&uri).unwrap().public_key().0)
+    }
+
+    // a fresh directory per test, they run in parallel
+    fn cache_dir(name: &str) -> String {
+        let dir = std::env::temp_dir().join(format!(
+            "substrate-benchmark-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        dir.to_string_lossy().into_owned()
+    }
+
+    // subkey inspect --scheme <scheme> //Alice, //Bob
+    #[test]
+    fn dev_accounts_match_subkey() {
+        let vectors = [
+            (
+                KeyScheme::Sr25519,
+                "//Alice",
+                "d43593c715fdd31c61141abd04a99fd6822c8558854ccde39a5684e7a56da27d",
+            ),
+            (
+                KeyScheme::Sr25519,
+                "//Bob",
+                "8eaf04151687736326c9fea17e25fc5287613693c912909cb226aa4794f26a48",
+            ),
+            (
+                KeyScheme::Sr25519,
+                "//Alice//stash",
+                "be5ddb1579b72e84524fc29e78609e3caf42e85aa118ebfe0b0ad404b5bdd25f",
+            ),
+            (
+                KeyScheme::Ed25519,
+                "//Alice",
+                "88dc3417d5058ec4b4503e0c12ea1a0a89be200fe98922423d4334014fa6b0ee",
+            ),
+            (
+                KeyScheme::Ed25519,
+                "//Bob",
+                "d17c2d7823ebf260fd138f2d7e27d114c0145d968b5ff5006125f2414fadae69",
+            ),
+            (
+                KeyScheme::Ecdsa,
+                "//Alice",
+                "020a1091341fe5664bfa1782d5e04779689068c916b04cb365ec3153755684d9a1",
+            ),
+            (
+                KeyScheme::Ecdsa,
+                "//Bob",
+                "0390084fdbf27d2b79d26a4f13f0ccd982cb755a661969143c37cbc49ef5b91f27",
+            ),
+        ];
+        for (scheme, uri, public) in vectors {
+            assert_eq!(public_hex(scheme, uri), public, "{:?} {}", scheme, uri);
+        }
+    }
+
+    #[test]
+    fn sr25519_soft_and_hard_paths_match_subxt_signer() {
+        for uri in [
+            "//Alice/soft",
+            "/soft",
+            "//Alice//bench-sender:7/1",
+            &format!("{}//hard/soft//0", DEV_PHRASE),
+            &format!("{}/soft///password", DEV_PHRASE),
+            "0x0000000000000000000000000000000000000000000000000000000000000001//bench/2",
+        ] {
+            assert_eq!(
+                public_hex(KeyScheme::Sr25519, uri),
+                sr25519_reference(uri),
+                "{}",
+                uri
+            );
+        }
+    }
+
+    #[test]
+    fn ecdsa_paths_match_subxt_signer() {
+        for uri in [
+            "//Alice//bench-sender:7",
+            &format!("{}//hard///password", DEV_PHRASE),
+        ] {
+            assert_eq!(
+                public_hex(KeyScheme::Ecdsa, uri),
+                ecdsa_reference(uri),
+                "{}",
+                uri
+            );
+        }
+    }
+
+    #[test]
+    fn ed25519_has_hard_junctions_only() {
+        let uri = SecretUri::from_str("//Alice/soft").unwrap();
+        assert!(AccountKeypair::from_uri(KeyScheme::Ed25519, &uri).is_err());
+        // a junction at a time is the same as the whole path
+        let alice = key(KeyScheme::Ed25519, "//Alice");
+        let derived = alice
+            .derive(&SecretUri::from_str("//stash").unwrap().junctions)
+            .unwrap();
+        assert_eq!(
+            derived.public_key(),
+            key(KeyScheme::Ed25519, "//Alice//stash").public_key()
+        );
+    }
+
+    #[test]
+    fn password_changes_the_key() {
+        for scheme in [KeyScheme::Sr25519, KeyScheme::Ed25519, KeyScheme::Ecdsa] {
+            assert_ne!(
+                public_hex(scheme, &format!("{}//0", DEV_PHRASE)),
+                public_hex(scheme, &format!("{}//0///password", DEV_PHRASE)),
+                "{:?}",
+                scheme
+            );
+        }
+    }
+
+    #[test]
+    fn secret_round_trips() {
+        for scheme in [KeyScheme::Sr25519, KeyScheme::Ed25519, KeyScheme::Ecdsa] {
+            let key = key(scheme, "//Alice//bench-sender:3");
+            let restored = AccountKeypair::from_secret(scheme, &key.secret()).unwrap();
+            assert_eq!(restored.public_key(), key.public_key(), "{:?}", scheme);
+            // ed25519 and ecdsa sign deterministically
+            if scheme != KeyScheme::Sr25519 {
+                assert_eq!(
+                    Signer::<SubstrateConfig>::sign(&restored, b"payload").encode(),
+                    Signer::<SubstrateConfig>::sign(&key, b"payload").encode()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn derive_matches_the_secret_uri() {
+        for scheme in [KeyScheme::Sr25519, KeyScheme::Ed25519, KeyScheme::Ecdsa] {
+            let config = KeyConfig {
+                scheme,
+                seed: DEV_PHRASE.to_string(),
+                password: Some("secret".to_string()),
+                ..Default::default()
+            };
+            let keys = config.derive("sender", 5).unwrap();
+            for (index, derived) in keys.iter().enumerate() {
+                let uri = config.secret_uri("sender", index as u32);
+                assert_eq!(
+                    derived.public_key(),
+                    key(scheme, &uri).public_key(),
+                    "{}",
+                    uri
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn cache_round_trips() {
+        for scheme in [KeyScheme::Sr25519, KeyScheme::Ed25519, KeyScheme::Ecdsa] {
+            let dir = cache_dir(&format!("cache-{:?}", scheme));
+            let config = KeyConfig {
+                scheme,
+                cache_dir: Some(dir.clone()),
+                ..Default::default()
+            };
+            let derived = config.derive("sender", 4).unwrap();
+            let path = config.cache_path("sender").unwrap();
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+            let cached = config.derive("sender", 3).unwrap();
+            assert_eq!(
+                cached.iter().map(|k| k.public_key()).collect::<Vec<_>>(),
+                derived[..3]
+                    .iter()
+                    .map(|k| k.public_key())
+                    .collect::<Vec<_>>()
+            );
+            assert_eq!(
+                config.derive_account_ids("sender", 4).unwrap(),
+                derived.iter().map(|k| k.account_id()).collect::<Vec<_>>()
+            );
+            fs::remove_dir_all(dir).unwrap();
+        }
+    }
+
+    #[test]
+    fn receiver_cache_keeps_no_secrets() {
+        let dir = cache_dir("receivers");
+        let config = KeyConfig {
+            cache_dir: Some(dir.clone()),
+            ..Default::default()
+        };
+        let ids = config.derive_account_ids("receiver", 3).unwrap();
+        // reclaim derives the receiver key pairs
+        let keys = config.derive("receiver", 3).unwrap();
+        assert_eq!(keys.iter().map(|k| k.account_id()).collect::<Vec<_>>(), ids);
+        let cache = config.read_cache("receiver", 3).unwrap();
+        assert!(cache.accounts.iter().all(|key| key.secret.is_none()));
+        fs::remove_dir_all(dir).unwrap();
+    }
 }
@@ -0,0 +1,329 @@
+use std::{fs, path::Path, str::FromStr};
+
+use anyhow::{anyhow, Result};
+use log::info;
+use serde::{Deserialize, Serialize};
+use subxt::utils::AccountId32;
+use subxt_signer::SecretUri;
+
+use crate::{
+    account::{AccountKeypair, KeyConfig, KeyScheme},
+    workload::encode_hex,
+};
+
+const CSV_HEADER: &str = "role,address,public_key,secret_uri";
+
+/// One account of an exported or imported list.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccountRecord {
+    // the derivation prefix, "sender" or "receiver"
+    pub role: String,
+    // ss58
+    pub address: String,
+    #[serde(default)]
+    pub public_key: String,
+    // only needed for accounts that sign
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret_uri: Option<String>,
+}
+
+enum Format {
+    Json,
+    Csv,
+}
+
+impl Format {
+    fn of(path: &str) -> Result<Self> {
+        match Path::new(path).extension().and_then(|e| e.to_str()) {
+            Some("json") => Ok(Format::Json),
+            Some("csv") => Ok(Format::Csv),
+            _ => Err(anyhow!("account list {} is neither .json nor .csv", path)),
+        }
+    }
+}
+
+/// Write the derived accounts of every `(prefix, num)` to `path`, secret uris only when
+/// asked for.
+pub fn export(
+    path: &str,
+    config: &KeyConfig,
+    sets: &[(&str, u32)],
+    with_secrets: bool,
+) -> Result<()> {
+    if config.import.is_some() {
+        return Err(anyhow!(
+            "export writes derived accounts, unset derivation.import"
+        ));
+    }
+    let mut records = Vec::new();
+    for (prefix, num) in sets {
+        let keys = config.derive(prefix, *num)?;
+        for (index, key) in keys.iter().enumerate() {
+            records.push(AccountRecord {
+                role: prefix.to_string(),
+                address: key.account_id().to_string(),
+                public_key: format!("0x{}", encode_hex(&key.public_key())),
+                secret_uri: with_secrets.then(|| config.secret_uri(prefix, index as u32)),
+            });
+        }
+    }
+
+    let content = match Format::of(path)? {
+        Format::Json => serde_json::to_string_pretty(&records)?,
+        Format::Csv => {
+            let mut lines = vec![CSV_HEADER.to_string()];
+            lines.extend(records.iter().map(csv_line));
+            lines.join("\n") + "\n"
+        }
+    };
+    fs::write(path, content)?;
+    info!("exported {} accounts to {}", records.len(), path);
+    Ok(())
+}
+
+/// The first `num` accounts of `role` in the list at `path`.
+pub fn load(path: &str, role: &str, num: u32) -> Result<Vec<AccountRecord>> {
+    let content = fs::read_to_string(path)?;
+    let records: Vec<AccountRecord> = match Format::of(path)? {
+        Format::Json => serde_json::from_str(&content)?,
+        Format::Csv => content
+            .lines()
+            .filter(|line| !line.trim().is_empty() && line.trim() != CSV_HEADER)
+            .map(parse_csv_line)
+            .collect::<Result<_>>()?,
+    };
+    let records = records
+        .into_iter()
+        .filter(|r| r.role == role)
+        .take(num as usize)
+        .collect::<Vec<_>>();
+    if records.len() < num as usize {
+        return Err(anyhow!(
+            "account list {} has {} {} accounts, {} needed",
+            path,
+            records.len(),
+            role,
+            num
+        ));
+    }
+    Ok(records)
+}
+
+/// Key pairs of imported accounts, every one needs a secret uri matching its address.
+pub fn load_keys(
+    path: &str,
+    scheme: KeyScheme,
+    role: &str,
+    num: u32,
+) -> Result<Vec<AccountKeypair>> {
+    load(path, role, num)?
+        .into_iter()
+        .map(|record| {
+            let uri = record
+                .secret_uri
+                .as_deref()
+                .filter(|uri| !uri.is_empty())
+                .ok_or(anyhow!(
+                    "imported {} {} has no secret uri",
+                    role,
+                    record.address
+                ))?;
+            let key = AccountKeypair::from_uri(scheme, &SecretUri::from_str(uri)?)?;
+            if key.account_id() != parse_address(&record.address)? {
+                return Err(anyhow!(
+                    "secret uri of {} derives another {:?} account",
+                    record.address,
+                    scheme
+                ));
+            }
+            Ok(key)
+        })
+        .collect()
+}
+
+/// Account ids of imported accounts.
+pub fn load_account_ids(path: &str, role: &str, num: u32) -> Result<Vec<AccountId32>> {
+    load(path, role, num)?
+        .iter()
+        .map(|record| parse_address(&record.address))
+        .collect()
+}
+
+fn parse_address(address: &str) -> Result<AccountId32> {
+    AccountId32::from_str(address).map_err(|e| anyhow!("invalid address {}: {:?}", address, e))
+}
+
+fn csv_line(r: &AccountRecord) -> String {
+    [
+        r.role.as_str(),
+        &r.address,
+        &r.public_key,
+        r.secret_uri.as_deref().unwrap_or(""),
+    ]
+    .map(csv_field)
+    .join(",")
+}
+
+// Quoted when it holds a separator or a quote or starts or ends with a space, passwords
+// may.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"']) || field.trim() != field {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn parse_csv_line(line: &str) -> Result<AccountRecord> {
+    // spaces around unquoted fields are dropped, hand written lists have them
+    let finish = |field: &mut String, was_quoted: bool| {
+        let field = std::mem::take(field);
+        if was_quoted {
+            field
+        } else {
+            field.trim().to_string()
+        }
+    };
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut was_quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' if quoted => quoted = false,
+            '"' if was_quoted || !field.trim().is_empty() => {
+                return Err(anyhow!("stray quote in an account list line: {}", line))
+            }
+            '"' => {
+                // only spaces before the opening quote
+                field.clear();
+                quoted = true;
+                was_quoted = true;
+            }
+            ',' if !quoted => {
+                fields.push(finish(&mut field, was_quoted));
+                was_quoted = false;
+            }
+            c if was_quoted && !quoted => {
+                if !c.is_whitespace() {
+                    return Err(anyhow!("text after a quoted field: {}", line));
+                }
+            }
+            c => field.push(c),
+        }
+    }
+    if quoted {
+        return Err(anyhow!("unterminated quote: {}", line));
+    }
+    fields.push(finish(&mut field, was_quoted));
+
+    let mut fields = fields.into_iter();
+    let role = fields.next().unwrap_or_default();
+    let address = fields
+        .next()
+        .filter(|a| !a.is_empty())
+        .ok_or(anyhow!("account list line without address: {}", line))?;
+    Ok(AccountRecord {
+        role,
+        address,
+        public_key: fields.next().unwrap_or_default(),
+        secret_uri: fields.next().filter(|s| !s.is_empty()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALICE: &str = "5GrwvaEF5zXb26Fz9rcQpDWS57CtERHpNehXCPcNoHGKutQY";
+
+    fn record(secret_uri: Option<&str>) -> AccountRecord {
+        AccountRecord {
+            role: "sender".to_string(),
+            address: ALICE.to_string(),
+            public_key: "0xd43593c715fdd31c61141abd04a99fd6822c8558854ccde39a5684e7a56da27d"
+                .to_string(),
+            secret_uri: secret_uri.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn csv_round_trips() {
+        for secret_uri in [
+            None,
+            Some("//Alice"),
+            Some("//bench-sender:1///pass,word"),
+            Some("//bench-sender:1///say \"hi\""),
+            Some("//bench-sender:1///\",\""),
+            Some("//bench-sender:1/// padded "),
+        ] {
+            let record = record(secret_uri);
+            let parsed = parse_csv_line(&csv_line(&record)).unwrap();
+            assert_eq!(parsed.role, record.role);
+            assert_eq!(parsed.address, record.address);
+            assert_eq!(parsed.public_key, record.public_key);
+            assert_eq!(parsed.secret_uri, record.secret_uri, "{:?}", secret_uri);
+        }
+    }
+
+    #[test]
+    fn csv_quotes_only_when_needed() {
+        assert_eq!(csv_field("//Alice"), "//Alice");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field(" x"), "\" x\"");
+    }
+
+    #[test]
+    fn csv_reads_hand_written_lines() {
+        let parsed = parse_csv_line(&format!(" receiver , {} ", ALICE)).unwrap();
+        assert_eq!(parsed.role, "receiver");
+        assert_eq!(parsed.address, ALICE);
+        assert_eq!(parsed.public_key, "");
+        assert_eq!(parsed.secret_uri, None);
+
+        let parsed = parse_csv_line(&format!("sender,{},, \"//x,y\" ", ALICE)).unwrap();
+        assert_eq!(parsed.secret_uri.as_deref(), Some("//x,y"));
+    }
+
+    #[test]
+    fn csv_rejects_malformed_lines() {
+        assert!(parse_csv_line("sender,,,").is_err());
+        assert!(parse_csv_line(&format!("sender,{},,\"//open", ALICE)).is_err());
+        assert!(parse_csv_line(&format!("sender,{},,\"//a\"b", ALICE)).is_err());
+        assert!(parse_csv_line(&format!("sender,{},,//a\"b", ALICE)).is_err());
+    }
+
+    #[test]
+    fn load_filters_by_role() {
+        let path = std::env::temp_dir().join(format!(
+            "substrate-benchmark-accounts-{}.csv",
+            std::process::id()
+        ));
+        let receiver = AccountRecord {
+            role: "receiver".to_string(),
+            ..record(None)
+        };
+        let lines = [
+            CSV_HEADER.to_string(),
+            csv_line(&record(Some("//Alice"))),
+            csv_line(&receiver),
+            csv_line(&receiver),
+        ];
+        fs::write(&path, lines.join("\n")).unwrap();
+        let path = path.to_string_lossy().into_owned();
+
+        assert_eq!(load(&path, "receiver", 2).unwrap().len(), 2);
+        assert!(load(&path, "receiver", 3).is_err());
+        let keys = load_keys(&path, KeyScheme::Sr25519, "sender", 1).unwrap();
+        assert_eq!(keys[0].account_id().to_string(), ALICE);
+        // a receiver has no secret uri to sign with
+        assert!(load_keys(&path, KeyScheme::Sr25519, "receiver", 1).is_err());
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -1,24 +1,31 @@
 use std::{
-    collections::HashMap,
-    sync::Arc,
+    collections::{BTreeMap, BTreeSet, HashMap},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, RwLock,
+    },
     time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use futures::lock::Mutex;
-use log::{debug, error, info};
+use log::{debug, error, info, warn};
 
 use subxt::{
     backend::legacy::LegacyRpcMethods,
-    backend::rpc::RpcClient,
+    backend::{rpc::RpcClient, StreamOfResults},
+    blocks::Block,
     client::OnlineClientT,
     config::substrate::H256,
+    dynamic::{At, Value},
+    ext::codec::{Compact, Decode, Encode},
     tx::{SubmittableExtrinsic, TxProgress},
     utils::AccountId32,
     Config, OnlineClient, SubstrateConfig,
 };
 
-use subxt_signer::sr25519::{Keypair, PublicKey};
+use subxt_signer::DeriveJunction;
+use tokio::{task::JoinHandle, time::Instant};
 
 #[cfg(feature = "substrate")]
 #[subxt::subxt(runtime_metadata_path = "metadata/substrate_metadata.scale")]
@@ -28,58 +35,199 @@ pub mod substrate {}
 #[subxt::subxt(runtime_metadata_path = "metadata/hotstuff_metadata.scale")]
 pub mod substrate {}
 
-use crate::metrics::Metrics;
+use crate::{
+    account::AccountKeypair,
+    destination::Destinations,
+    estimate::{ceiling_tps, BlockLimits, CallEstimate, RESOURCES},
+    funding::{Funder, Payer},
+    metrics::{percentile, Metrics, RunStatus},
+    read_bench::ReadQuery,
+    retry::{classify, ErrorKind, RetryPolicy},
+    watch::{self, Timeouts},
+    workload::{
+        decode_hex, dynamic_transfer, gas_limit, load_contract_code, optional_balance,
+        AssetTransfer, CallContext, ContractCall, Mixed, Workload, WorkloadConfig,
+    },
+};
+
+type BenchClient = OnlineClient<SubstrateConfig>;
+type BenchExtrinsic = SubmittableExtrinsic<SubstrateConfig, BenchClient>;
+type BlockStream = StreamOfResults<Block<SubstrateConfig, BenchClient>>;
+// estimated fees are multiplied by this when funding, fees rise as blocks fill up
+const FEE_MARGIN: u128 = 4;
+
+// what the contract deployer gets before uploading (1000 units of a 12 decimals token),
+// it is funded again with the others
+const DEPLOYER_FUNDING: u128 = 1_000_000_000_000_000;
+
+// storage keys per state_queryStorageAt request
+const STORAGE_QUERY_CHUNK: usize = 1000;
+
+// a funding batch may use this share of the block limits (in percent)
+const FUNDING_BATCH_BLOCK_SHARE: u64 = 75;
+
+// block usage from which a resource counts as the bottleneck
+const SATURATION_PERCENT: f64 = 90.0;
+
+// finalized blocks the block time is averaged over
+const BLOCK_TIME_SAMPLE: u32 = 20;
+
+type WatchHandle = JoinHandle<Result<Option<H256>, subxt::Error>>;
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("get system")
+        .as_millis() as u64
+}
 
 pub struct Client {
-    // send transaction
-    api: OnlineClient<SubstrateConfig>,
+    url: String,
+    // send transaction, replaced when the websocket is reopened
+    api: RwLock<BenchClient>,
     // call chain rpc method
-    _rpc: LegacyRpcMethods<SubstrateConfig>,
+    rpc: RwLock<LegacyRpcMethods<SubstrateConfig>>,
+    // bumped after every reconnect, so concurrent tasks reconnect only once
+    generation: AtomicU64,
+    reconnecting: Mutex<()>,
+    // applied to every transaction submission
+    retry: RetryPolicy,
+    // applied to every watched transaction
+    timeouts: Timeouts,
+    // set on SIGINT/SIGTERM, tasks stop sending new transactions
+    stop: Arc<AtomicBool>,
+    // besides head and tail, watch every n-th transaction. 0 = head and tail only
+    watch_every: usize,
 
     pub metric: Arc<Mutex<Metrics>>,
 }
 
 impl Client {
-    pub async fn new(url: &str, metric: Arc<Mutex<Metrics>>) -> Result<Self> {
-        let api = OnlineClient::<SubstrateConfig>::from_url(url).await?;
+    pub async fn new(
+        url: &str,
+        metric: Arc<Mutex<Metrics>>,
+        retry: RetryPolicy,
+        timeouts: Timeouts,
+        stop: Arc<AtomicBool>,
+        watch_every: usize,
+    ) -> Result<Self> {
+        let (api, rpc) = Self::connect(url).await?;
+
+        Ok(Self {
+            url: url.to_string(),
+            api: RwLock::new(api),
+            rpc: RwLock::new(rpc),
+            generation: AtomicU64::new(0),
+            reconnecting: Mutex::new(()),
+            retry,
+            timeouts,
+            stop,
+            watch_every,
+            metric,
+        })
+    }
+
+    async fn connect(url: &str) -> Result<(BenchClient, LegacyRpcMethods<SubstrateConfig>)> {
+        let api = BenchClient::from_url(url).await?;
 
         let rpc_client = RpcClient::from_url(url).await?;
         let rpc = LegacyRpcMethods::<SubstrateConfig>::new(rpc_client);
 
-        Ok(Self {
-            api,
-            _rpc: rpc,
-            metric: metric,
-        })
+        Ok((api, rpc))
+    }
+
+    fn api(&self) -> BenchClient {
+        self.api.read().expect("api lock").clone()
+    }
+
+    fn rpc(&self) -> LegacyRpcMethods<SubstrateConfig> {
+        self.rpc.read().expect("rpc lock").clone()
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::SeqCst)
+    }
+
+    /// Reopen the websocket with backoff. `seen_generation` is the generation the caller
+    /// was using when it saw the failure; if another task already reconnected since then,
+    /// this returns straight away.
+    pub async fn reconnect(&self, seen_generation: u64) -> Result<()> {
+        let _guard = self.reconnecting.lock().await;
+        if self.generation() != seen_generation {
+            return Ok(());
+        }
+
+        let outage_begin = now_millis();
+        warn!("lost connection to {}, reconnecting", self.url);
+
+        let mut attempt = 0;
+        let (api, rpc) = loop {
+            match Self::connect(&self.url).await {
+                Ok(conn) => break conn,
+                Err(e) => {
+                    attempt += 1;
+                    if attempt >= self.retry.reconnect_max_attempts {
+                        return Err(anyhow!(
+                            "reconnect to {} failed after {} attempts: {}",
+                            self.url,
+                            attempt,
+                            e
+                        ));
+                    }
+                    debug!(
+                        "reconnect to {} failed {}, attempt {}",
+                        self.url, e, attempt
+                    );
+                    tokio::time::sleep(self.retry.backoff(attempt)).await;
+                }
+            }
+        };
+
+        *self.api.write().expect("api lock") = api;
+        *self.rpc.write().expect("rpc lock") = rpc;
+        self.generation.fetch_add(1, Ordering::SeqCst);
+
+        let outage_end = now_millis();
+        info!(
+            "reconnected to {} after {}ms",
+            self.url,
+            outage_end - outage_begin
+        );
+        self.metric
+            .lock()
+            .await
+            .add_outage(&self.url, outage_begin, outage_end);
+
+        Ok(())
     }
 
     pub async fn charge_balance_to_account(
         &self,
-        sudo: &Keypair,
-        targets: &[PublicKey],
+        sudo: &AccountKeypair,
+        targets: &[AccountId32],
         amount: u128,
     ) -> Result<()> {
         type Call = substrate::runtime_types::node_template_runtime::RuntimeCall;
         type BalanceCall = substrate::runtime_types::pallet_balances::pallet::Call;
-        let mut nonce = self
-            .api
-            .tx()
-            .account_nonce(&AccountId32::from(sudo.public_key()))
-            .await?;
+        let api = self.api();
+        let mut nonce = api.tx().account_nonce(&sudo.account_id()).await?;
 
         let mut submittable_txs = Vec::new();
 
         for target in targets.iter() {
             let call = Call::Balances(BalanceCall::force_set_balance {
-                who: PublicKey(target.0.clone()).into(),
+                who: target.clone().into(),
                 new_free: amount,
             });
             let tx = substrate::tx().sudo().sudo(call);
 
-            let tx =
-                self.api
-                    .tx()
-                    .create_signed_with_nonce(&tx, sudo, nonce, Default::default())?;
+            let tx = api
+                .tx()
+                .create_signed_with_nonce(&tx, sudo, nonce, Default::default())?;
 
             submittable_txs.push(tx);
             nonce += 1;
@@ -88,172 +236,957 @@ impl Client {
         self.submit_txs_and_wait_finalize(submittable_txs).await
     }
 
-    pub async fn batch_balance_transfer(
+    /// Bring every target to `amount`, through sudo or from the faucet. Targets that
+    /// still hold `top_up_below_percent` of it from an earlier run are skipped.
+    pub async fn fund_accounts(
         &self,
-        task_name: String,
-        sender: &Keypair,
-        receiver: PublicKey,
-        tx_number: u32,
+        funder: &Funder,
+        targets: &[AccountId32],
         amount: u128,
     ) -> Result<()> {
-        let balance_transfer_tx = substrate::tx()
+        let accounts = targets.to_vec();
+        let threshold = amount / 100 * u128::from(funder.top_up_below_percent);
+        let missing = self
+            .free_balances(&accounts)
+            .await?
+            .into_iter()
+            .zip(accounts)
+            .filter(|(free, _)| *free < threshold)
+            .map(|(free, account)| (account, amount.saturating_sub(free)))
+            .collect::<Vec<_>>();
+        info!(
+            "{} of {} accounts hold enough already, funding {}",
+            targets.len() - missing.len(),
+            targets.len(),
+            missing.len()
+        );
+        if missing.is_empty() {
+            return Ok(());
+        }
+
+        match &funder.payer {
+            Payer::Sudo(sudo) => {
+                let targets = missing
+                    .iter()
+                    .map(|(account, _)| account.clone())
+                    .collect::<Vec<_>>();
+                self.charge_balance_to_account(sudo, &targets, amount).await
+            }
+            Payer::Faucet {
+                faucet,
+                chunk_size,
+                fan_out_above,
+            } => {
+                self.fund_from_faucet(faucet, missing, *chunk_size, *fan_out_above)
+                    .await
+            }
+        }
+    }
+
+    /// Free balance of every account, read with `state_queryStorageAt` in chunks instead
+    /// of one request per account.
+    async fn free_balances(&self, accounts: &[AccountId32]) -> Result<Vec<u128>> {
+        let keys = self.account_storage_keys(accounts)?;
+        let at = self.api().blocks().at_latest().await?.hash();
+        let mut balances = HashMap::new();
+        for chunk in keys.chunks(STORAGE_QUERY_CHUNK) {
+            let change_sets = self
+                .rpc()
+                .state_query_storage_at(chunk.iter().map(|k| k.as_slice()), Some(at))
+                .await?;
+            for (key, data) in change_sets.into_iter().flat_map(|c| c.changes) {
+                if let Some(data) = data {
+                    // AccountInfo { nonce, consumers, providers, sufficients, data: { free, .. } }
+                    let (_, _, _, _, free) =
+                        <(u32, u32, u32, u32, u128)>::decode(&mut data.0.as_slice())?;
+                    balances.insert(key.0, free);
+                }
+            }
+        }
+        // accounts that do not exist have no entry
+        Ok(keys
+            .iter()
+            .map(|key| balances.get(key).copied().unwrap_or(0))
+            .collect())
+    }
+
+    /// Pay the targets with `utility.batch_all` of `transfer_keep_alive`. Past
+    /// `fan_out_above` targets, the faucet first funds one intermediate account per chunk
+    /// and the intermediates then pay their chunk in parallel.
+    async fn fund_from_faucet(
+        &self,
+        faucet: &AccountKeypair,
+        targets: Vec<(AccountId32, u128)>,
+        chunk_size: u32,
+        fan_out_above: u32,
+    ) -> Result<()> {
+        let chunk_size = self.fitting_chunk_size(faucet, chunk_size).await?;
+        if targets.len() <= fan_out_above as usize {
+            let txs = self.transfer_batches(faucet, &targets, chunk_size).await?;
+            return self.submit_txs_and_wait_success(txs).await;
+        }
+
+        let chunks = targets.chunks(chunk_size).collect::<Vec<_>>();
+        // derived from the faucet, so only the faucet owner can spend what is left on them
+        let intermediates = (0..chunks.len() as u32)
+            .map(|i| {
+                faucet.derive(&[
+                    DeriveJunction::hard("intermediate"),
+                    DeriveJunction::hard(i),
+                ])
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let existential_deposit = self.existential_deposit()?;
+        info!(
+            "funding {} accounts through {} intermediate accounts",
+            targets.len(),
+            intermediates.len()
+        );
+
+        // Sign the second level first, its fees decide what the intermediates need.
+        let mut second_level = Vec::new();
+        let mut intermediate_funding = Vec::new();
+        for (intermediate, chunk) in intermediates.iter().zip(chunks) {
+            let mut txs = self
+                .transfer_batches(intermediate, chunk, chunk_size)
+                .await?;
+            let tx = txs.pop().expect("one batch per chunk");
+            let fee = self.query_info(&tx).await?.partial_fee;
+            let total: u128 = chunk.iter().map(|(_, amount)| amount).sum();
+            intermediate_funding.push((
+                intermediate.account_id(),
+                total + fee * FEE_MARGIN + existential_deposit,
+            ));
+            second_level.push(tx);
+        }
+
+        let txs = self
+            .transfer_batches(faucet, &intermediate_funding, chunk_size)
+            .await?;
+        self.submit_txs_and_wait_success(txs).await?;
+
+        let results = futures::future::join_all(
+            second_level
+                .into_iter()
+                .map(|tx| self.submit_txs_and_wait_success(vec![tx])),
+        )
+        .await;
+        results.into_iter().collect()
+    }
+
+    /// The configured chunk size, halved until a full batch stays within
+    /// `FUNDING_BATCH_BLOCK_SHARE` of the block weight and length limits.
+    async fn fitting_chunk_size(&self, from: &AccountKeypair, chunk_size: u32) -> Result<usize> {
+        let limits = self.block_limits()?.resources();
+        let sample = from.account_id();
+        let mut chunk_size = chunk_size.max(1) as usize;
+        loop {
+            let targets = vec![(sample.clone(), 0); chunk_size];
+            let tx = self
+                .transfer_batches(from, &targets, chunk_size)
+                .await?
+                .pop()
+                .expect("one batch");
+            let estimate = self.query_info(&tx).await?;
+            let used = [estimate.ref_time, estimate.proof_size, estimate.length];
+            let fits = used
+                .iter()
+                .zip(limits)
+                .all(|(used, limit)| used * 100 <= limit * FUNDING_BATCH_BLOCK_SHARE);
+            if fits || chunk_size == 1 {
+                return Ok(chunk_size);
+            }
+            chunk_size /= 2;
+        }
+    }
+
+    /// `utility.batch_all` txs of `transfer_keep_alive`, `chunk_size` transfers each,
+    /// signed with consecutive nonces of `from`.
+    async fn transfer_batches(
+        &self,
+        from: &AccountKeypair,
+        targets: &[(AccountId32, u128)],
+        chunk_size: usize,
+    ) -> Result<Vec<BenchExtrinsic>> {
+        let api = self.api();
+        let mut nonce = api.tx().account_nonce(&from.account_id()).await?;
+        let mut txs = Vec::new();
+        for chunk in targets.chunks(chunk_size) {
+            let calls = chunk
+                .iter()
+                .map(|(target, amount)| dynamic_transfer(target, *amount).into_value())
+                .collect::<Vec<_>>();
+            let batch = subxt::dynamic::tx(
+                "Utility",
+                "batch_all",
+                vec![Value::unnamed_composite(calls)],
+            );
+            txs.push(
+                api.tx()
+                    .create_signed_with_nonce(&batch, from, nonce, Default::default())?,
+            );
+            nonce += 1;
+        }
+        Ok(txs)
+    }
+
+    /// Like `submit_txs_and_wait_finalize`, but every tx has to finalize and succeed.
+    async fn submit_txs_and_wait_success(&self, txs: Vec<BenchExtrinsic>) -> Result<()> {
+        let mut tx_processes = Vec::new();
+        for tx in txs.iter() {
+            let (process, retries) = self.retry.run(|| tx.submit_and_watch()).await;
+            self.metric.lock().await.add_retries(retries);
+            tx_processes.push(process?);
+        }
+        let results = futures::future::join_all(
+            tx_processes
+                .into_iter()
+                .map(|p| watch::wait_for_finalized(p, &self.timeouts)),
+        )
+        .await;
+        for res in results {
+            res?.ok_or(anyhow!("tx did not finalize in time"))?
+                .wait_for_success()
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Send everything the bench accounts hold back to `collector` with
+    /// `balances.transfer_all`, all accounts in parallel. Prints what came back and which
+    /// accounts failed.
+    pub async fn reclaim(
+        &self,
+        accounts: &[AccountKeypair],
+        collector: &AccountId32,
+    ) -> Result<()> {
+        let ids = accounts.iter().map(|k| k.account_id()).collect::<Vec<_>>();
+        let holding = self
+            .free_balances(&ids)
+            .await?
+            .into_iter()
+            .zip(accounts)
+            .filter(|(free, _)| *free > 0)
+            .collect::<Vec<_>>();
+        let held: u128 = holding.iter().map(|(free, _)| free).sum();
+        info!(
+            "reclaim {} from {} of {} bench accounts to {}",
+            held,
+            holding.len(),
+            accounts.len(),
+            collector
+        );
+        let collector_before = self.free_balances(std::slice::from_ref(collector)).await?[0];
+
+        let results = futures::future::join_all(
+            holding
+                .iter()
+                .map(|(_, account)| self.sweep_account(account, collector)),
+        )
+        .await;
+        let failed = holding
+            .iter()
+            .zip(results)
+            .filter_map(|((free, account), res)| {
+                res.err().map(|e| (account.account_id(), *free, e))
+            })
+            .collect::<Vec<_>>();
+
+        let collector_after = self.free_balances(std::slice::from_ref(collector)).await?[0];
+        println!("***** reclaim report *****");
+        println!(
+            "collector {}: recovered {} of {} held by {} accounts",
+            collector,
+            collector_after.saturating_sub(collector_before),
+            held,
+            holding.len()
+        );
+        println!("failed accounts: {}", failed.len());
+        for (account, free, e) in failed {
+            println!("  {} (holding {}): {}", account, free, e);
+        }
+        Ok(())
+    }
+
+    async fn sweep_account(&self, account: &AccountKeypair, collector: &AccountId32) -> Result<()> {
+        let tx = substrate::tx()
             .balances()
-            .transfer_allow_death(receiver.into(), amount);
+            .transfer_all(collector.clone().into(), false);
+        let tx = self
+            .api()
+            .tx()
+            .create_signed(&tx, account, Default::default())
+            .await?;
+        self.submit_txs_and_wait_success(vec![tx]).await
+    }
 
-        let mut nonce = self
-            .api
+    /// Build the workload, first setting up whatever it needs on chain: the contract of
+    /// `contracts_call` is deployed by the first of the funded `senders`, the asset of the
+    /// assets workloads is created by `sudo` and minted to all senders.
+    pub async fn prepare_workload(
+        &self,
+        config: &WorkloadConfig,
+        sudo: &AccountKeypair,
+        funder: &Funder,
+        senders: &[AccountKeypair],
+    ) -> Result<Box<dyn Workload>> {
+        config.validate()?;
+        match config {
+            WorkloadConfig::Mixed { seed, profile } => {
+                let mut workloads = Vec::new();
+                for p in profile {
+                    let workload = self
+                        .prepare_single(&p.workload, sudo, funder, senders)
+                        .await?;
+                    workloads.push((p.weight, workload));
+                }
+                Ok(Box::new(Mixed::new(*seed, workloads)?))
+            }
+            _ => self.prepare_single(config, sudo, funder, senders).await,
+        }
+    }
+
+    async fn prepare_single(
+        &self,
+        config: &WorkloadConfig,
+        sudo: &AccountKeypair,
+        funder: &Funder,
+        senders: &[AccountKeypair],
+    ) -> Result<Box<dyn Workload>> {
+        match config {
+            WorkloadConfig::AssetsTransfer {
+                asset_id,
+                amount,
+                mint,
+            }
+            | WorkloadConfig::AssetsTransferKeepAlive {
+                asset_id,
+                amount,
+                mint,
+            } => {
+                self.mint_asset_to_accounts(sudo, *asset_id, senders, (*mint).into())
+                    .await?;
+                let keep_alive = matches!(config, WorkloadConfig::AssetsTransferKeepAlive { .. });
+                Ok(Box::new(AssetTransfer::new(
+                    *asset_id,
+                    (*amount).into(),
+                    keep_alive,
+                )))
+            }
+            WorkloadConfig::ContractsCall {
+                code_path,
+                constructor,
+                input,
+                gas_ref_time,
+                gas_proof_size,
+                storage_deposit_limit,
+                value,
+            } => {
+                let deployer = senders
+                    .first()
+                    .ok_or(anyhow!("no sender to deploy the contract"))?;
+                self.fund_accounts(funder, &[deployer.account_id()], DEPLOYER_FUNDING)
+                    .await?;
+                let code = load_contract_code(code_path.as_deref())?;
+                let contract = self
+                    .instantiate_contract(
+                        deployer,
+                        code,
+                        decode_hex(constructor)?,
+                        gas_limit(*gas_ref_time, *gas_proof_size),
+                    )
+                    .await?;
+                Ok(Box::new(ContractCall::new(
+                    contract,
+                    decode_hex(input)?,
+                    (*value).into(),
+                    *gas_ref_time,
+                    *gas_proof_size,
+                    storage_deposit_limit.map(u128::from),
+                )))
+            }
+            _ => config.build(),
+        }
+    }
+
+    /// Create `asset_id` with `sudo` as owner unless it exists already (a rerun), then mint
+    /// `amount` to every target, through `sudo_as` when another account issues it. The asset
+    /// is sufficient, so receivers need no native balance to hold it. Fails when the asset
+    /// is missing or a target holds less than before plus `amount` afterwards.
+    async fn mint_asset_to_accounts(
+        &self,
+        sudo: &AccountKeypair,
+        asset_id: u32,
+        targets: &[AccountKeypair],
+        amount: u128,
+    ) -> Result<()> {
+        let api = self.api();
+        let sudo_account = sudo.account_id();
+        let asset_query =
+            subxt::dynamic::storage("Assets", "Asset", vec![Value::u128(asset_id.into())]);
+        let existing = api.storage().at_latest().await?.fetch(&asset_query).await?;
+        // mint needs the issuer origin, the second field of AssetDetails
+        let issuer = match existing {
+            Some(details) => <(AccountId32, AccountId32)>::decode(&mut details.encoded())?.1,
+            None => {
+                let force_create = subxt::dynamic::tx(
+                    "Assets",
+                    "force_create",
+                    vec![
+                        Value::u128(asset_id.into()),
+                        Value::unnamed_variant("Id", [Value::from_bytes(sudo_account.0)]),
+                        Value::bool(true),
+                        Value::u128(1),
+                    ],
+                );
+                let tx = subxt::dynamic::tx("Sudo", "sudo", vec![force_create.into_value()]);
+                let nonce = api.tx().account_nonce(&sudo_account).await?;
+                let tx = api
+                    .tx()
+                    .create_signed_with_nonce(&tx, sudo, nonce, Default::default())?;
+                self.submit_txs_and_wait_finalize(vec![tx]).await?;
+                // the sudo tx succeeds even when force_create fails
+                if self.asset_min_balance(asset_id).await?.is_none() {
+                    return Err(anyhow!("creating asset {} failed", asset_id));
+                }
+                info!("created asset {}", asset_id);
+                sudo_account.clone()
+            }
+        };
+        if issuer != sudo_account {
+            info!(
+                "asset {} is issued by {}, mint with sudo_as",
+                asset_id, issuer
+            );
+        }
+
+        let mut before = Vec::new();
+        for target in targets {
+            before.push(self.asset_balance(asset_id, &target.account_id()).await?);
+        }
+        let mut nonce = api.tx().account_nonce(&sudo_account).await?;
+        let mut submittable_txs = Vec::new();
+        for target in targets {
+            let mint = subxt::dynamic::tx(
+                "Assets",
+                "mint",
+                vec![
+                    Value::u128(asset_id.into()),
+                    Value::unnamed_variant("Id", [Value::from_bytes(target.account_id().0)]),
+                    Value::u128(amount),
+                ],
+            );
+            let mint = if issuer == sudo_account {
+                mint
+            } else {
+                subxt::dynamic::tx(
+                    "Sudo",
+                    "sudo_as",
+                    vec![
+                        Value::unnamed_variant("Id", [Value::from_bytes(issuer.0)]),
+                        mint.into_value(),
+                    ],
+                )
+            };
+            let tx = api
+                .tx()
+                .create_signed_with_nonce(&mint, sudo, nonce, Default::default())?;
+            submittable_txs.push(tx);
+            nonce += 1;
+        }
+        self.submit_txs_and_wait_finalize(submittable_txs).await?;
+
+        // failed mints only show up as Sudid / SudoAsDone errors, read the balances back
+        for (target, before) in targets.iter().zip(before) {
+            let balance = self.asset_balance(asset_id, &target.account_id()).await?;
+            if balance < before.saturating_add(amount) {
+                return Err(anyhow!(
+                    "minting asset {} to {} failed, it holds {}",
+                    asset_id,
+                    target.account_id(),
+                    balance
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Upload and instantiate a contract, returning its address.
+    async fn instantiate_contract(
+        &self,
+        deployer: &AccountKeypair,
+        code: Vec<u8>,
+        data: Vec<u8>,
+        gas_limit: Value,
+    ) -> Result<AccountId32> {
+        // a fresh salt, so reruns against the same chain get a new contract
+        let salt = now_millis().to_le_bytes();
+        let tx = subxt::dynamic::tx(
+            "Contracts",
+            "instantiate_with_code",
+            vec![
+                Value::u128(0),
+                gas_limit,
+                optional_balance(None),
+                Value::from_bytes(code),
+                Value::from_bytes(data),
+                Value::from_bytes(salt),
+            ],
+        );
+        let tx = self
+            .api()
             .tx()
-            .account_nonce(&AccountId32::from(sender.public_key()))
+            .create_signed(&tx, deployer, Default::default())
             .await?;
+        let (process, retries) = self.retry.run(|| tx.submit_and_watch()).await;
+        self.metric.lock().await.add_retries(retries);
+        let in_block = watch::wait_for_finalized(process?, &self.timeouts)
+            .await?
+            .ok_or(anyhow!("contract instantiation timed out"))?;
+        let events = in_block.wait_for_success().await?;
+        for event in events.iter() {
+            let event = event?;
+            // Instantiated { deployer, contract }
+            if event.pallet_name() == "Contracts" && event.variant_name() == "Instantiated" {
+                let fields = event.field_bytes();
+                if fields.len() >= 64 {
+                    let mut contract = [0u8; 32];
+                    contract.copy_from_slice(&fields[32..64]);
+                    let contract = AccountId32(contract);
+                    info!("contract instantiated at {}", contract);
+                    return Ok(contract);
+                }
+            }
+        }
+        Err(anyhow!("no Contracts.Instantiated event"))
+    }
+
+    pub async fn batch_workload_transfer(
+        &self,
+        task_name: String,
+        workload: &dyn Workload,
+        sender: &AccountKeypair,
+        sender_index: usize,
+        destinations: &Destinations,
+        tx_number: u32,
+    ) -> Result<()> {
+        let api = self.api();
+        let sender_account = sender.account_id();
+        let start_nonce = api.tx().account_nonce(&sender_account).await?;
 
         let mut pending_txs = Vec::new();
+        let mut kinds = Vec::new();
 
-        for _i in 0..tx_number {
-            let created_tx = self.api.tx().create_signed_with_nonce(
-                &balance_transfer_tx,
-                sender,
+        let mut receivers = BTreeSet::new();
+
+        for (tx_index, nonce) in (start_nonce..start_nonce + tx_number as u64).enumerate() {
+            let receiver = destinations.pick(sender_index, tx_index, &sender_account, nonce);
+            receivers.insert(receiver.clone());
+            let call = workload.call(&CallContext {
+                sender: &sender_account,
+                receiver,
                 nonce,
-                Default::default(),
-            )?;
+            })?;
+            let created_tx =
+                api.tx()
+                    .create_signed_with_nonce(&call, sender, nonce, Default::default())?;
             pending_txs.push(created_tx);
-            nonce += 1;
+            kinds.push(call.kind().to_string());
+        }
+
+        {
+            let mut metric = self.metric.lock().await;
+            metric.set_workload(workload.name());
+            metric.set_destination(destinations.name());
+            metric.add_receivers(receivers);
         }
 
         // submit_txs_and_wait_finalize(pending_txs).await
-        self.submit_txs_then_watch_head_and_tail(task_name, pending_txs).await
+        self.submit_txs_then_watch_head_and_tail(
+            task_name,
+            &sender_account,
+            start_nonce,
+            pending_txs,
+            kinds,
+        )
+        .await
     }
 
-    async fn submit_txs_then_watch_head_and_tail<T: Config, C: OnlineClientT<T>>(
+    async fn submit_txs_then_watch_head_and_tail(
         &self,
         task_name: String,
-        txs: Vec<SubmittableExtrinsic<T, C>>,
+        sender: &AccountId32,
+        start_nonce: u64,
+        mut txs: Vec<BenchExtrinsic>,
+        kinds: Vec<String>,
     ) -> Result<()> {
         let mut num = 0;
-        let mut first_tx_process: Option<TxProgress<T, C>> = None;
-        let mut last_tx_process: Option<TxProgress<T, C>> = None;
-
-        let begin_send = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .expect("get system")
-            .as_millis() as u64;
-        info!("task_name: {}. begin send transaction {}", task_name, begin_send);
+        // which txs made it into the pool, for the per call kind counts
+        let mut sent = vec![false; txs.len()];
+        // watches are tagged with the client generation they were opened on
+        let mut first_tx_process: Option<(WatchHandle, u64)> = None;
+        let mut last_tx_process: Option<(WatchHandle, u64)> = None;
+        let mut sampled_tx_processes = Vec::new();
+        let mut first_tx_failed = false;
+        let mut last_tx_failed = false;
+
+        let begin_send = now_millis();
+        info!(
+            "task_name: {}. begin send transaction {}",
+            task_name, begin_send
+        );
         {
             let mut metric = self.metric.lock().await;
             metric.set_begin_timestamp(begin_send)
         }
 
-        for (index, transaction) in txs.iter().enumerate() {
-            if index % 500 == 0{
+        let mut retries = 0;
+        let mut failed = 0;
+        let mut generation = self.generation();
+        let mut index = 0;
+        while index < txs.len() {
+            if self.stop.load(Ordering::SeqCst) {
+                info!("task_name: {}. stop sending at tx {}", task_name, index);
+                break;
+            }
+            let transaction = &txs[index];
+            if index % 500 == 0 {
                 info!("task_name: {}. already send {}", task_name, num);
             }
-            if index == 0 {
-                match transaction.submit_and_watch().await {
+            let is_head_or_tail = index == 0 || index == txs.len() - 1;
+            let is_sampled = self.watch_every > 0 && index % self.watch_every == 0;
+            let err = if is_head_or_tail || is_sampled {
+                let submitted_at = Instant::now();
+                let (res, r) = self.retry.run(|| transaction.submit_and_watch()).await;
+                retries += r;
+                match res {
                     Ok(p) => {
-                        first_tx_process = Some(p);
+                        let handle = self.spawn_watch(p, submitted_at, kinds[index].clone());
+                        if index == 0 {
+                            first_tx_process = Some((handle, generation));
+                        } else if index == txs.len() - 1 {
+                            last_tx_process = Some((handle, generation));
+                        } else {
+                            sampled_tx_processes.push(handle);
+                        }
                         num += 1;
+                        None
                     }
-                    Err(e) => error!("submit first tx failed {}", e),
-                }
-            } else if index == txs.len() - 1 {
-                loop{
-                    match transaction.submit_and_watch().await {
-                        Ok(p) => {
-                            last_tx_process = Some(p);
-                            num += 1;
-                            break;
-                        }
-                        Err(e) => error!("task_name {}, submit last tx failed {}",task_name, e),
+                    // an earlier attempt reached the pool, the watch is lost, head and
+                    // tail fall back to following the nonce
+                    Err(e) if classify(&e) == ErrorKind::AlreadyImported => {
+                        num += 1;
+                        None
                     }
+                    Err(e) => Some(e),
                 }
             } else {
-                match transaction.submit().await {
-                    Ok(_msg) => num += 1,
-                    Err(e) => {
-                        info!("task_name:{} ,submit error  {}", task_name, e);
+                let (res, r) = self.retry.run(|| transaction.submit()).await;
+                retries += r;
+                match res {
+                    Ok(_msg) => {
+                        num += 1;
+                        None
                     }
+                    Err(e) if classify(&e) == ErrorKind::AlreadyImported => {
+                        num += 1;
+                        None
+                    }
+                    Err(e) => Some(e),
+                }
+            };
+
+            match err {
+                None => {
+                    sent[index] = true;
+                    index += 1
+                }
+                Some(e) if classify(&e) == ErrorKind::Disconnected => {
+                    warn!(
+                        "task_name: {}, connection lost at tx {}: {}",
+                        task_name, index, e
+                    );
+                    self.reconnect(generation).await?;
+                    generation = self.generation();
+
+                    // The node may have restarted with an empty pool, so continue from the
+                    // next nonce it knows about and re-bind the signed txs to the new client.
+                    let api = self.api();
+                    let next_nonce = api.tx().account_nonce(sender).await?;
+                    index = (next_nonce.saturating_sub(start_nonce) as usize).min(txs.len());
+                    num = num.min(index as u32);
+                    first_tx_failed &= index > 0;
+                    for (i, s) in sent.iter_mut().enumerate() {
+                        *s = i < index;
+                    }
+                    txs = txs
+                        .into_iter()
+                        .map(|tx| SubmittableExtrinsic::from_bytes(api.clone(), tx.into_encoded()))
+                        .collect();
+                    info!("task_name: {}, resume from tx {}", task_name, index);
+                }
+                Some(e) => {
+                    failed += 1;
+                    if index == 0 {
+                        first_tx_failed = true;
+                    } else if index == txs.len() - 1 {
+                        last_tx_failed = true;
+                    }
+                    warn!("task_name: {}, submit tx {} failed {}", task_name, index, e);
+                    index += 1;
                 }
             }
         }
-        let end_send = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .expect("get system")
-            .as_millis() as u64;
+        let end_send = now_millis();
         info!(
             "task_name: {}. end send transaction {}, send txs duration: {}s",
             task_name,
             end_send,
             Duration::from_millis(end_send - begin_send).as_secs()
         );
-        info!("task_name: {}. has successfully submit txs, num {}", task_name,num);
+        info!(
+            "task_name: {}. has successfully submit txs, num {}, retries {}, failed {}",
+            task_name, num, retries, failed
+        );
 
-        let first_tx_process = first_tx_process.unwrap();
-        let last_tx_process = last_tx_process.unwrap();
+        {
+            let mut metric = self.metric.lock().await;
+            metric.add_tx_number(num);
+            metric.add_retries(retries);
+            metric.add_failed_tx(failed);
+            for (i, tx) in txs.iter().enumerate().filter(|(i, _)| sent[*i]) {
+                metric.add_kind_submitted(&kinds[i], 1, tx.encoded().len() as u64);
+            }
+        }
 
-        match first_tx_process.wait_for_finalized().await {
-            Ok(res) => {
+        if index == 0 {
+            return Ok(());
+        }
+        // when stopped early, the last tx is the last one that was sent
+        let last_nonce = start_nonce + index as u64 - 1;
+        if first_tx_failed {
+            return Err(anyhow!(
+                "task_name: {}. first tx was not submitted",
+                task_name
+            ));
+        }
+        if last_tx_failed {
+            return Err(anyhow!(
+                "task_name: {}. last tx was not submitted",
+                task_name
+            ));
+        }
+
+        match self
+            .wait_tx_finalized(first_tx_process, sender, start_nonce)
+            .await
+        {
+            Ok(Some(include_block_hash)) => {
                 let mut metric = self.metric.lock().await;
-                let include_block_hash = H256::from_slice(res.block_hash().as_ref());
                 metric.set_begin_block(include_block_hash)
             }
-            Err(e) => error!("task_name:{}, latest tx has error {}",task_name,e),
+            Ok(None) => {
+                warn!("task_name: {}, first tx timed out", task_name);
+                let mut metric = self.metric.lock().await;
+                metric.add_timed_out_tx(1);
+                metric.set_status(RunStatus::TimedOut);
+            }
+            Err(e) => error!("task_name:{}, latest tx has error {}", task_name, e),
         }
 
-        match last_tx_process.wait_for_finalized().await {
-            Ok(res) => {
-                info!("task_name: {}, last tx finalize block at {:#?}", task_name, res.block_hash());
+        match self
+            .wait_tx_finalized(last_tx_process, sender, last_nonce)
+            .await
+        {
+            Ok(None) => {
+                warn!("task_name: {}, last tx timed out", task_name);
+                let mut metric = self.metric.lock().await;
+                metric.add_timed_out_tx(1);
+                metric.set_status(RunStatus::TimedOut);
+            }
+            Ok(Some(latest_tx_finalize_hash)) => {
+                info!(
+                    "task_name: {}, last tx finalize block at {:#?}",
+                    task_name, latest_tx_finalize_hash
+                );
                 let mut metric = self.metric.lock().await;
-                let finalize_end = SystemTime::now()
-                    .duration_since(UNIX_EPOCH)
-                    .expect("get system")
-                    .as_millis() as u64;
+                let finalize_end = now_millis();
 
-                let latest_tx_finalize_hash = H256::from_slice(res.block_hash().as_ref());
                 metric.set_finalize_block(latest_tx_finalize_hash);
                 metric.set_end_timestamp(finalize_end)
             }
             Err(e) => error!("task_name:{} ,latest tx has error {}", task_name, e),
         }
 
-        let mut metric = self.metric.lock().await;
-        metric.add_tx_number(num);
+        let mut sampled_timed_out = 0;
+        for handle in sampled_tx_processes {
+            if let Ok(Ok(None)) = handle.await {
+                sampled_timed_out += 1;
+            }
+        }
+        self.metric.lock().await.add_timed_out_tx(sampled_timed_out);
 
         Ok(())
     }
 
+    /// Follow a watched tx in the background, so its status transitions are timed while
+    /// the task keeps sending. The lifecycle ends up in the metrics.
+    fn spawn_watch(
+        &self,
+        process: TxProgress<SubstrateConfig, BenchClient>,
+        submitted_at: Instant,
+        kind: String,
+    ) -> WatchHandle {
+        let timeouts = self.timeouts.clone();
+        let metric = self.metric.clone();
+        tokio::spawn(async move {
+            let (lifecycle, res) = watch::track(process, &timeouts, submitted_at).await;
+            metric.lock().await.record_lifecycle(&kind, &lifecycle);
+            res.map(|r| r.map(|s| H256::from_slice(s.block_hash().as_ref())))
+        })
+    }
+
+    /// Wait for a watched tx to be finalized, `None` if it timed out. If the watch was lost
+    /// with the connection, fall back to following finalized blocks until the sender's nonce
+    /// passes `nonce`, then search back for the block that included it.
+    async fn wait_tx_finalized(
+        &self,
+        process: Option<(WatchHandle, u64)>,
+        sender: &AccountId32,
+        nonce: u64,
+    ) -> Result<Option<H256>> {
+        if let Some((handle, generation)) = process {
+            match handle.await? {
+                Ok(res) => return Ok(res),
+                // reconnect is a no-op if the watch belonged to an already replaced client
+                Err(e) if classify(&e) == ErrorKind::Disconnected => {
+                    warn!("watch of tx with nonce {} lost: {}", nonce, e);
+                    self.reconnect(generation).await?;
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+        let limit = self.timeouts.inclusion + self.timeouts.finality;
+        let seen = match tokio::time::timeout(limit, self.wait_nonce_finalized(sender, nonce)).await
+        {
+            Ok(res) => res?,
+            Err(_) => return Ok(None),
+        };
+        // the nonce may have moved long before the fallback started following blocks
+        self.inclusion_block(sender, nonce, seen).await.map(Some)
+    }
+
+    /// The first block up to `seen` after which `sender` has used `nonce`, searched between
+    /// the run start and `seen`: the block that included the tx.
+    async fn inclusion_block(&self, sender: &AccountId32, nonce: u64, seen: H256) -> Result<H256> {
+        let api = self.api();
+        let run_start = self.metric.lock().await.run_start_block;
+        let mut low = match run_start {
+            Some(hash) => api.blocks().at(hash).await?.header().number,
+            None => 0,
+        };
+        let mut high = api.blocks().at(seen).await?.header().number;
+        let mut high_hash = seen;
+        let account_query = substrate::storage().system().account(sender);
+        // the nonce is used at high and not yet at low
+        while low + 1 < high {
+            let mid = low + (high - low) / 2;
+            let hash = self
+                .rpc()
+                .chain_get_block_hash(Some(mid.into()))
+                .await?
+                .ok_or(anyhow!("no block #{}", mid))?;
+            let account = api
+                .storage()
+                .at(hash)
+                .fetch_or_default(&account_query)
+                .await?;
+            if u64::from(account.nonce) > nonce {
+                high = mid;
+                high_hash = hash;
+            } else {
+                low = mid;
+            }
+        }
+        Ok(high_hash)
+    }
+
+    /// The first finalized block (seen from now on) where `sender` has used `nonce`.
+    async fn wait_nonce_finalized(&self, sender: &AccountId32, nonce: u64) -> Result<H256> {
+        let account_query = substrate::storage().system().account(sender);
+        loop {
+            let generation = self.generation();
+            let mut finalized_sub = self.api().blocks().subscribe_finalized().await?;
+            while let Some(block) = finalized_sub.next().await {
+                let block = match block {
+                    Ok(block) => block,
+                    Err(e) if classify(&e) == ErrorKind::Disconnected => break,
+                    Err(e) => return Err(e.into()),
+                };
+                let account = self
+                    .api()
+                    .storage()
+                    .at(block.hash())
+                    .fetch_or_default(&account_query)
+                    .await?;
+                if u64::from(account.nonce) > nonce {
+                    return Ok(block.hash());
+                }
+            }
+            self.reconnect(generation).await?;
+        }
+    }
+
     pub async fn submit_txs_and_wait_finalize<T: Config, C: OnlineClientT<T>>(
         &self,
         txs: Vec<SubmittableExtrinsic<T, C>>,
     ) -> Result<()> {
         let mut tx_processes = Vec::new();
+        let mut retries = 0;
         for submittable_tx in txs.iter() {
-            let process = submittable_tx.submit_and_watch().await?;
-
-            tx_processes.push(process);
+            let (process, r) = self.retry.run(|| submittable_tx.submit_and_watch()).await;
+            retries += r;
+            match process {
+                Ok(process) => tx_processes.push(process),
+                Err(e) => {
+                    self.metric.lock().await.add_retries(retries);
+                    return Err(e.into());
+                }
+            }
         }
+        self.metric.lock().await.add_retries(retries);
 
         let mut ti = Vec::new();
         for tp in tx_processes {
-            ti.push(tp.wait_for_finalized());
+            ti.push(watch::wait_for_finalized(tp, &self.timeouts));
         }
 
         let res = futures::future::join_all(ti).await;
 
         let mut num = 0;
+        let mut timed_out = 0;
         for tx_info in res.iter() {
             match tx_info {
-                Ok(_) => num += 1,
+                Ok(Some(_)) => num += 1,
+                Ok(None) => timed_out += 1,
                 Err(e) => info!("{}", e),
             }
         }
 
-        info!("submit_txs_and_wait_finalize done. finalize num {}", num);
+        info!(
+            "submit_txs_and_wait_finalize done. finalize num {}, timed out {}",
+            num, timed_out
+        );
+        if timed_out > 0 {
+            warn!("{} txs did not finalize in time", timed_out);
+        }
 
         Ok(())
     }
 
     async fn get_block_timestamp(&self, block_hash: H256) -> Result<u64> {
         let block_timestamp_query = substrate::storage().timestamp().now();
-        self.api
+        self.api()
             .storage()
             .at(block_hash)
             .fetch(&block_timestamp_query)
@@ -262,12 +1195,279 @@ impl Client {
                 "get current block timestamp should work",
             ))
     }
+
+    /// Per block limits of normal dispatches, which is what every benchmark tx is.
+    fn block_limits(&self) -> Result<BlockLimits> {
+        let api = self.api();
+        let weights = api
+            .constants()
+            .at(&substrate::constants().system().block_weights())?;
+        let length = api
+            .constants()
+            .at(&substrate::constants().system().block_length())?;
+        let minimum_period = api
+            .constants()
+            .at(&substrate::constants().timestamp().minimum_period())?;
+        let normal = &weights.per_class.normal;
+        let max_total = normal.max_total.as_ref().unwrap_or(&weights.max_block);
+        Ok(BlockLimits {
+            ref_time: max_total.ref_time,
+            proof_size: max_total.proof_size,
+            length: length.max.normal.into(),
+            base_extrinsic_ref_time: normal.base_extrinsic.ref_time,
+            base_extrinsic_proof_size: normal.base_extrinsic.proof_size,
+            block_time_ms: minimum_period * 2,
+        })
+    }
+
+    /// Average interval of the last finalized blocks from their timestamps. The minimum
+    /// period only gives the slot of aura, hotstuff takes its interval from the node
+    /// (HOTSTUFF_DURATION). `fallback` on a chain too young to measure.
+    async fn measured_block_time_ms(&self, fallback: u64) -> Result<u64> {
+        let api = self.api();
+        let latest = api.blocks().at_latest().await?;
+        let number = latest.header().number;
+        // the genesis block has no timestamp
+        if number < 2 {
+            return Ok(fallback);
+        }
+        let span = BLOCK_TIME_SAMPLE.min(number - 1);
+        let earlier = self
+            .rpc()
+            .chain_get_block_hash(Some((number - span).into()))
+            .await?
+            .ok_or(anyhow!("no block #{}", number - span))?;
+        let elapsed = self.get_block_timestamp(latest.hash()).await?
+            - self.get_block_timestamp(earlier).await?;
+        let block_time = elapsed / u64::from(span);
+        info!(
+            "block time {}ms over the last {} blocks, the timestamp minimum period says {}ms",
+            block_time, span, fallback
+        );
+        Ok(block_time.max(1))
+    }
+
+    /// Ask the runtime what one sample tx of every call kind of the workload costs, and
+    /// derive the highest TPS the block limits allow. Nothing is submitted.
+    pub async fn estimate_workload(
+        &self,
+        workload: &dyn Workload,
+        sender: &AccountKeypair,
+        receiver: &AccountId32,
+    ) -> Result<BTreeMap<String, CallEstimate>> {
+        let api = self.api();
+        let sender_account = sender.account_id();
+        let nonce = api.tx().account_nonce(&sender_account).await?;
+
+        let mut calls = Vec::new();
+        let mut estimates = BTreeMap::new();
+        for (weight, part) in workload.profile().unwrap_or_else(|| vec![(1, workload)]) {
+            let call = part.call(&CallContext {
+                sender: &sender_account,
+                receiver,
+                nonce,
+            })?;
+            let tx = api
+                .tx()
+                .create_signed_with_nonce(&call, sender, nonce, Default::default())?;
+            let estimate = self.query_info(&tx).await?;
+            calls.push((weight, estimate.clone()));
+            estimates.insert(call.kind().to_string(), estimate);
+        }
+
+        let mut limits = self.block_limits()?;
+        limits.block_time_ms = self.measured_block_time_ms(limits.block_time_ms).await?;
+        let (tps, bound) = ceiling_tps(&limits, &calls);
+        info!("theoretical max tps {:.1}, bound by {}", tps, bound);
+        self.metric
+            .lock()
+            .await
+            .set_estimates(estimates.clone(), tps, bound);
+        Ok(estimates)
+    }
+
+    /// `TransactionPaymentApi_query_info` of a signed tx.
+    async fn query_info(&self, tx: &BenchExtrinsic) -> Result<CallEstimate> {
+        let mut params = tx.encoded().to_vec();
+        (tx.encoded().len() as u32).encode_to(&mut params);
+        // RuntimeDispatchInfo { weight: { ref_time, proof_size }, class, partial_fee }
+        let (ref_time, proof_size, _class, partial_fee) = self
+            .api()
+            .runtime_api()
+            .at_latest()
+            .await?
+            .call_raw::<(Compact<u64>, Compact<u64>, u8, u128)>(
+                "TransactionPaymentApi_query_info",
+                Some(&params),
+            )
+            .await?;
+        Ok(CallEstimate {
+            partial_fee,
+            ref_time: ref_time.0,
+            proof_size: proof_size.0,
+            length: tx.encoded().len() as u64,
+        })
+    }
+
+    fn existential_deposit(&self) -> Result<u128> {
+        Ok(self
+            .api()
+            .constants()
+            .at(&substrate::constants().balances().existential_deposit())?)
+    }
+
+    /// `min_balance` of an asset, `None` if it does not exist.
+    async fn asset_min_balance(&self, asset_id: u32) -> Result<Option<u128>> {
+        let asset_query =
+            subxt::dynamic::storage("Assets", "Asset", vec![Value::u128(asset_id.into())]);
+        let Some(details) = self
+            .api()
+            .storage()
+            .at_latest()
+            .await?
+            .fetch(&asset_query)
+            .await?
+        else {
+            return Ok(None);
+        };
+        Ok(details
+            .to_value()?
+            .at("min_balance")
+            .and_then(|v| v.as_u128()))
+    }
+
+    /// Asset balance of `account`, 0 without an asset account.
+    async fn asset_balance(&self, asset_id: u32, account: &AccountId32) -> Result<u128> {
+        let account_query = subxt::dynamic::storage(
+            "Assets",
+            "Account",
+            vec![Value::u128(asset_id.into()), Value::from_bytes(account.0)],
+        );
+        let Some(asset_account) = self
+            .api()
+            .storage()
+            .at_latest()
+            .await?
+            .fetch(&account_query)
+            .await?
+        else {
+            return Ok(0);
+        };
+        Ok(asset_account
+            .to_value()?
+            .at("balance")
+            .and_then(|v| v.as_u128())
+            .unwrap_or(0))
+    }
+
+    /// Fail before anything is sent if a transfer of the workload is below the existential
+    /// deposit or the minimum of an existing asset, the chain would reject every tx to a
+    /// new receiver with `ExistentialDeposit` / `BelowMinimum`.
+    pub async fn check_transfer_amounts(&self, config: &WorkloadConfig) -> Result<()> {
+        let existential_deposit = self.existential_deposit()?;
+        for amount in config.native_transfers() {
+            if amount < existential_deposit {
+                return Err(anyhow!(
+                    "transfer amount {} is below the existential deposit {}",
+                    amount,
+                    existential_deposit
+                ));
+            }
+        }
+        for (asset_id, amount) in config.asset_transfers() {
+            if let Some(min_balance) = self.asset_min_balance(asset_id).await? {
+                if amount < min_balance {
+                    return Err(anyhow!(
+                        "transfer amount {} is below the min balance {} of asset {}",
+                        amount,
+                        min_balance,
+                        asset_id
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Balance every sender needs for `tx_per_sender` txs: the highest estimated fee, with
+    /// room for the fee multiplier to climb while blocks are full, plus what each call
+    /// spends, on top of the existential deposit that keeps the sender alive.
+    pub fn funding_amount(
+        &self,
+        workload: &dyn Workload,
+        estimates: &BTreeMap<String, CallEstimate>,
+        tx_per_sender: u32,
+    ) -> Result<u128> {
+        let max_fee = estimates.values().map(|e| e.partial_fee).max().unwrap_or(0);
+        let per_tx = max_fee * FEE_MARGIN + workload.native_spend();
+        let funding = self.existential_deposit()? + per_tx * u128::from(tx_per_sender);
+        info!(
+            "funding every sender with {} (fee up to {}, spend {} per tx)",
+            funding,
+            max_fee,
+            workload.native_spend()
+        );
+        Ok(funding)
+    }
+
+    /// Weight consumed by a block over all dispatch classes, as (ref_time, proof_size).
+    async fn get_block_weight(&self, block_hash: H256) -> Result<(u64, u64)> {
+        let block_weight_query = substrate::storage().system().block_weight();
+        let weight = self
+            .api()
+            .storage()
+            .at(block_hash)
+            .fetch_or_default(&block_weight_query)
+            .await?;
+        let classes = [weight.normal, weight.operational, weight.mandatory];
+        Ok((
+            classes.iter().map(|w| w.ref_time).sum(),
+            classes.iter().map(|w| w.proof_size).sum(),
+        ))
+    }
     #[allow(dead_code)]
     async fn get_current_block(&self) -> Result<H256> {
-        let hash = self.api.blocks().at_latest().await?.hash();
+        let hash = self.api().blocks().at_latest().await?.hash();
         Ok(hash)
     }
 
+    /// Next item of a head subscription. When the subscription ends or the connection
+    /// drops, reconnect and subscribe again; `None` only once reconnecting gave up.
+    async fn next_block(
+        &self,
+        sub: &mut BlockStream,
+        finalized: bool,
+    ) -> Option<Result<Block<SubstrateConfig, BenchClient>, subxt::Error>> {
+        loop {
+            let generation = self.generation();
+            match sub.next().await {
+                Some(Err(e)) if classify(&e) == ErrorKind::Disconnected => {
+                    warn!("head subscription of {} dropped: {}", self.url, e)
+                }
+                Some(res) => return Some(res),
+                None => warn!("head subscription of {} ended", self.url),
+            }
+
+            if let Err(e) = self.reconnect(generation).await {
+                error!("{}", e);
+                return None;
+            }
+            let blocks = self.api().blocks();
+            let resubscribed = if finalized {
+                blocks.subscribe_finalized().await
+            } else {
+                blocks.subscribe_best().await
+            };
+            match resubscribed {
+                Ok(new_sub) => *sub = new_sub,
+                Err(e) => {
+                    error!("resubscribe heads of {} failed: {}", self.url, e);
+                    return None;
+                }
+            }
+        }
+    }
+
     pub async fn stat_finalize_speed(&self) -> Result<()> {
         println!("\n begin stats finalize speed");
 
@@ -277,13 +1477,13 @@ impl Client {
         let mut best_block_timestamp = HashMap::new();
         let mut finalize_block_timestamp = HashMap::new();
 
-        let mut best_blocks_sub = self.api.blocks().subscribe_best().await?;
-        let mut finalize_blocks_sub = self.api.blocks().subscribe_finalized().await?;
+        let mut best_blocks_sub = self.api().blocks().subscribe_best().await?;
+        let mut finalize_blocks_sub = self.api().blocks().subscribe_finalized().await?;
 
         futures::future::join(
             async {
                 let mut first = true;
-                while let Some(block) = best_blocks_sub.next().await {
+                while let Some(block) = self.next_block(&mut best_blocks_sub, false).await {
                     if let Ok(block) = block {
                         if first{
                             first = false;
@@ -298,13 +1498,7 @@ impl Client {
                             block.extrinsics().await.unwrap().len()
                         );
 
-                        best_block_timestamp.insert(
-                            block_number,
-                            SystemTime::now()
-                                .duration_since(UNIX_EPOCH)
-                                .expect("get system")
-                                .as_millis() as u64,
-                        );
+                        best_block_timestamp.insert(block_number, now_millis());
                         best_stat_number -= 1;
                         if best_stat_number == 0{
                             break;
@@ -316,7 +1510,7 @@ impl Client {
             async {
                 let mut first = true;
 
-                while let Some(block) = finalize_blocks_sub.next().await {
+                while let Some(block) = self.next_block(&mut finalize_blocks_sub, true).await {
                     if let Ok(block) = block {
                         if first{
                             first = false;
@@ -333,13 +1527,7 @@ impl Client {
                             block.extrinsics().await.unwrap().len()
                         );
 
-                        finalize_block_timestamp.insert(
-                            block_number,
-                            SystemTime::now()
-                                .duration_since(UNIX_EPOCH)
-                                .expect("get system")
-                                .as_millis() as u64,
-                        );
+                        finalize_block_timestamp.insert(block_number, now_millis());
                         finalize_stat_number -= 1;
                     }
                 }
@@ -397,42 +1585,215 @@ impl Client {
         Ok(())
     }
 
-    pub async fn report(&self) -> Result<()> {
-        println!("***** benchmark report *****");
+    /// Storage key of `System.Account` for each account, what the read bench queries.
+    pub fn account_storage_keys(&self, accounts: &[AccountId32]) -> Result<Vec<Vec<u8>>> {
+        let api = self.api();
+        accounts
+            .iter()
+            .map(|account| {
+                let query = substrate::storage().system().account(account);
+                Ok(api.storage().address_bytes(&query)?)
+            })
+            .collect()
+    }
 
+    /// One read of the read bench, straight through the legacy rpc methods so no client
+    /// side caching or decoding is measured.
+    pub async fn read_query(
+        &self,
+        query: ReadQuery,
+        storage_key: &[u8],
+    ) -> Result<(), subxt::Error> {
+        let rpc = self.rpc();
+        match query {
+            ReadQuery::Storage => {
+                rpc.state_get_storage(storage_key, None).await?;
+            }
+            ReadQuery::Block => {
+                rpc.chain_get_block(None).await?;
+            }
+            ReadQuery::RuntimeVersion => {
+                rpc.state_get_runtime_version(None).await?;
+            }
+            ReadQuery::Health => {
+                rpc.system_health().await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Remember the finalized head before any tx is sent, so a partial report has
+    /// somewhere to start from.
+    pub async fn mark_run_start(&self) -> Result<()> {
+        let hash = self.api().blocks().at_latest().await?.hash();
+        self.metric.lock().await.set_run_start_block(hash);
+        Ok(())
+    }
+
+    pub async fn report(&self) -> Result<()> {
         let metric = self.metric.lock().await;
-        let begin_block_hash = metric.first_tx_begin_block.unwrap();
-        let end_block_hash = metric.last_tx_finalize_block.unwrap();
+        println!("***** benchmark report ({}) *****", metric.status);
+
+        // Without a finalized first / last tx (timeouts, interrupt) fall back to the run
+        // start and the current finalized head.
+        let completed = metric.status == RunStatus::Completed;
+        let begin_block_hash = match metric.first_tx_begin_block.or(metric.run_start_block) {
+            Some(hash) => hash,
+            // stopped during setup or funding, nothing was sent
+            None if !completed => {
+                println!("workload: {}", metric.workload);
+                println!("network: {}", metric.network);
+                println!("stopped before the run started, no tx sent");
+                return Ok(());
+            }
+            None => return Err(anyhow!("no block observed for the begin of the run")),
+        };
+        let end_block_hash = match metric.last_tx_finalize_block {
+            Some(hash) if completed => hash,
+            _ => self.api().blocks().at_latest().await?.hash(),
+        };
         let total_tx = metric.total_tx;
 
         let begin_time = metric.begin_send;
-        let finalize_time = metric.finalize_end;
+        let finalize_time = if completed && metric.finalize_end > begin_time {
+            metric.finalize_end
+        } else {
+            now_millis()
+        };
         // let begin_time = self.get_block_timestamp(begin_block_hash).await?;
         //let finalize_time = self.get_block_timestamp(end_block_hash).await?;
 
         let duration = Duration::from_millis(finalize_time - begin_time).as_secs() as u32;
-        let tps = f64::from(total_tx) / f64::from(duration);
+        let tps = f64::from(total_tx) / f64::from(duration.max(1));
 
+        println!("workload: {}", metric.workload);
+        println!(
+            "destination: {}. distinct receivers: {}",
+            metric.destination,
+            metric.receivers.len()
+        );
+        println!("network: {}", metric.network);
         println!(
             "begin block timestamp: {}. end block timestamp {}. duration {}s. total tx: {}. tps: {}",
             begin_time, finalize_time, duration, total_tx, tps
         );
+        for (kind, estimate) in metric.estimates.iter() {
+            println!(
+                "estimate {}: partial fee {}, weight ref_time {}, proof_size {}, length {} bytes",
+                kind, estimate.partial_fee, estimate.ref_time, estimate.proof_size, estimate.length
+            );
+        }
+        if let Some((ceiling, bound)) = metric.ceiling_tps {
+            println!(
+                "theoretical max tps: {:.1} (bound by {}). achieved {:.1}% of it",
+                ceiling,
+                bound,
+                tps * 100.0 / ceiling.max(f64::EPSILON)
+            );
+        }
+        if metric.timed_out_tx > 0 {
+            println!("watched tx timed out: {}", metric.timed_out_tx);
+        }
+        println!(
+            "submit retries: {}. failed tx after retries: {}",
+            metric.retries, metric.failed_tx
+        );
+        for outage in metric.outages.iter() {
+            println!(
+                "node {} unreachable from {} to {}, outage {}ms",
+                outage.url,
+                outage.begin,
+                outage.end,
+                outage.end - outage.begin
+            );
+        }
+
+        for (kind, stats) in metric.kinds.iter() {
+            let mut finalized = stats.finalized.clone();
+            finalized.sort_unstable();
+            println!(
+                "call {}: submitted {} ({:.1}%). avg extrinsic size: {} bytes. tps: {:.2}. finalized p50 {}ms, p95 {}ms over {} watched",
+                kind,
+                stats.submitted,
+                f64::from(stats.submitted) * 100.0 / f64::from(total_tx.max(1)),
+                stats.bytes / u64::from(stats.submitted.max(1)),
+                f64::from(stats.submitted) / f64::from(duration.max(1)),
+                percentile(&finalized, 50.0),
+                percentile(&finalized, 95.0),
+                finalized.len()
+            );
+        }
+        let transfers_of = |prefix: &str| -> u32 {
+            metric
+                .kinds
+                .iter()
+                .filter(|(kind, _)| kind.starts_with(prefix))
+                .map(|(_, stats)| stats.submitted)
+                .sum()
+        };
+        let asset_transfers = transfers_of("assets_transfer");
+        if asset_transfers > 0 {
+            println!(
+                "asset transfer tps: {:.2}. native transfer tps: {:.2}",
+                f64::from(asset_transfers) / f64::from(duration.max(1)),
+                f64::from(transfers_of("transfer_")) / f64::from(duration.max(1))
+            );
+        }
+
+        let lifecycle = &metric.lifecycle;
+        if !lifecycle.terminal.is_empty() {
+            let outcomes = lifecycle
+                .terminal
+                .iter()
+                .map(|(terminal, count)| format!("{}: {}", terminal.name(), count))
+                .collect::<Vec<_>>()
+                .join(", ");
+            println!(
+                "watched tx outcome: {}. retracted from best chain: {}",
+                outcomes, lifecycle.retracted
+            );
+            for (status, samples) in [
+                ("validated", &lifecycle.validated),
+                ("broadcasted", &lifecycle.broadcasted),
+                ("in best block", &lifecycle.in_best_block),
+                ("finalized", &lifecycle.finalized),
+            ] {
+                let mut sorted = samples.clone();
+                sorted.sort_unstable();
+                println!(
+                    "submit -> {}: count {}, p50 {}ms, p95 {}ms, max {}ms",
+                    status,
+                    sorted.len(),
+                    percentile(&sorted, 50.0),
+                    percentile(&sorted, 95.0),
+                    sorted.last().copied().unwrap_or(0)
+                );
+            }
+        }
 
         let mut hash = end_block_hash;
         let mut block_stats = Vec::new();
 
         loop {
-            let block = self.api.blocks().at(hash).await?;
+            let block = self.api().blocks().at(hash).await?;
             let block_number = block.header().number;
             let block_hash = block.hash();
 
             hash = block.header().parent_hash;
 
+            let extrinsics = block.extrinsics().await?;
+            let mut length = 0;
+            for ext in extrinsics.iter() {
+                length += ext?.bytes().len() as u64;
+            }
+            let (ref_time, proof_size) = self.get_block_weight(block_hash).await?;
+
             block_stats.push((
                 block_number,
                 block_hash,
                 self.get_block_timestamp(block_hash).await?,
-                block.extrinsics().await?.len(),
+                extrinsics.len(),
+                [ref_time, proof_size, length],
             ));
 
             if block_hash == begin_block_hash {
@@ -440,9 +1801,53 @@ impl Client {
             }
         }
 
-        for (number, hash, timestamp, tx_size) in block_stats.iter().rev() {
+        for (number, hash, timestamp, tx_size, [ref_time, proof_size, length]) in
+            block_stats.iter().rev()
+        {
+            println!(
+                "Block #{number}, Hash: {hash}, timestamp: {timestamp},Transaction size: {tx_size}, weight ref_time: {ref_time}, proof_size: {proof_size}, length: {length}"
+            );
+        }
+
+        // Usage against the limits of normal dispatches. The resource with the highest peak
+        // is the one that caps the chain.
+        let limits = self.block_limits()?.resources();
+        let mut usages = Vec::new();
+        for (i, name) in RESOURCES.into_iter().enumerate() {
+            let used = block_stats
+                .iter()
+                .map(|(_, _, _, _, usage)| usage[i] as f64 * 100.0 / limits[i].max(1) as f64)
+                .collect::<Vec<_>>();
+            let peak = used.iter().copied().fold(0.0, f64::max);
+            let avg = used.iter().sum::<f64>() / used.len().max(1) as f64;
+            println!(
+                "block {} usage: peak {:.1}%, avg {:.1}% of {}",
+                name, peak, avg, limits[i]
+            );
+            usages.push((name, peak));
+        }
+        if let Some((name, peak)) = usages.iter().max_by(|a, b| a.1.total_cmp(&b.1)) {
+            if *peak >= SATURATION_PERCENT {
+                println!("saturated resource: {} ({:.1}%)", name, peak);
+            } else {
+                println!(
+                    "no block resource saturated, closest is {} ({:.1}%)",
+                    name, peak
+                );
+            }
+        }
+
+        if !completed {
+            // every block carries the timestamp inherent
+            let included: usize = block_stats
+                .iter()
+                .map(|(_, _, _, tx_size, _)| tx_size.saturating_sub(1))
+                .sum();
             println!(
-                "Block #{number}, Hash: {hash}, timestamp: {timestamp},Transaction size: {tx_size}"
+                "partial run ({}): {} txs included in the {} observed blocks",
+                metric.status,
+                included,
+                block_stats.len()
             );
         }
 
@@ -0,0 +1,205 @@
+use anyhow::{anyhow, Result};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use rand_distr::{Distribution, Zipf};
+use serde::Deserialize;
+use subxt::{ext::codec::Encode, utils::AccountId32};
+
+/// The `[destination]` table of the config file, how a sender picks the receiver of
+/// each tx.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum DestinationConfig {
+    /// Sender i always sends to receiver i (modulo the receiver count).
+    #[default]
+    Fixed,
+    /// Each sender walks over all receivers, starting at its own index.
+    RoundRobin,
+    /// Any receiver with the same probability.
+    Uniform {
+        #[serde(default)]
+        seed: u64,
+    },
+    /// Rank k receives with probability ~ 1 / k^exponent, a few hot accounts take most txs.
+    Zipf {
+        #[serde(default = "default_exponent")]
+        exponent: f64,
+        #[serde(default)]
+        seed: u64,
+    },
+    /// Senders pay each other, uniformly over all other senders.
+    SenderToSender {
+        #[serde(default)]
+        seed: u64,
+    },
+}
+
+fn default_exponent() -> f64 {
+    1.0
+}
+
+impl DestinationConfig {
+    pub fn build(
+        &self,
+        senders: Vec<AccountId32>,
+        receivers: Vec<AccountId32>,
+    ) -> Result<Destinations> {
+        let (pool, picker) = match *self {
+            DestinationConfig::Fixed => (receivers, Picker::Fixed),
+            DestinationConfig::RoundRobin => (receivers, Picker::RoundRobin),
+            DestinationConfig::Uniform { seed } => (receivers, Picker::Uniform { seed }),
+            DestinationConfig::Zipf { exponent, seed } => {
+                let zipf = Zipf::new(receivers.len() as u64, exponent)
+                    .map_err(|e| anyhow!("invalid zipf destination: {}", e))?;
+                (
+                    receivers,
+                    Picker::Zipf {
+                        zipf,
+                        exponent,
+                        seed,
+                    },
+                )
+            }
+            DestinationConfig::SenderToSender { seed } => {
+                if senders.len() < 2 {
+                    return Err(anyhow!("sender_to_sender needs at least two senders"));
+                }
+                (senders.clone(), Picker::SenderToSender { seed })
+            }
+        };
+        if pool.is_empty() {
+            return Err(anyhow!("no receiver accounts"));
+        }
+        Ok(Destinations { pool, picker })
+    }
+}
+
+enum Picker {
+    Fixed,
+    RoundRobin,
+    Uniform {
+        seed: u64,
+    },
+    Zipf {
+        zipf: Zipf<f64>,
+        exponent: f64,
+        seed: u64,
+    },
+    SenderToSender {
+        seed: u64,
+    },
+}
+
+/// Receivers of the benchmark txs and the rule to pick one per tx.
+pub struct Destinations {
+    pool: Vec<AccountId32>,
+    picker: Picker,
+}
+
+impl Destinations {
+    pub fn name(&self) -> String {
+        match &self.picker {
+            Picker::Fixed => format!("fixed over {} receivers", self.pool.len()),
+            Picker::RoundRobin => format!("round_robin over {} receivers", self.pool.len()),
+            Picker::Uniform { .. } => format!("uniform over {} receivers", self.pool.len()),
+            Picker::Zipf { exponent, .. } => {
+                format!("zipf(s={}) over {} receivers", exponent, self.pool.len())
+            }
+            Picker::SenderToSender { .. } => {
+                format!("sender_to_sender over {} senders", self.pool.len())
+            }
+        }
+    }
+
+    /// Receiver of the `tx_index`-th tx of sender `sender_index`. Random pickers are seeded
+    /// with the sender and the nonce, so reruns send to the same accounts.
+    pub fn pick(
+        &self,
+        sender_index: usize,
+        tx_index: usize,
+        sender: &AccountId32,
+        nonce: u64,
+    ) -> &AccountId32 {
+        let len = self.pool.len();
+        let index = match &self.picker {
+            Picker::Fixed => sender_index % len,
+            Picker::RoundRobin => (sender_index + tx_index) % len,
+            Picker::Uniform { seed } => tx_rng(b"dest", *seed, sender, nonce).gen_range(0..len),
+            // ranks start at 1, the hottest account is the first receiver
+            Picker::Zipf { zipf, seed, .. } => {
+                (zipf.sample(&mut tx_rng(b"dest", *seed, sender, nonce)) as usize - 1).min(len - 1)
+            }
+            Picker::SenderToSender { seed } => {
+                // skip the sender itself
+                let other = tx_rng(b"dest", *seed, sender, nonce).gen_range(0..len - 1);
+                if other >= sender_index {
+                    other + 1
+                } else {
+                    other
+                }
+            }
+        };
+        &self.pool[index]
+    }
+}
+
+/// An RNG for one tx, seeded with the config seed, the sender and the nonce. `domain`
+/// tells the uses apart, so e.g. the receiver and the call kind of a tx are independent.
+pub fn tx_rng(domain: &[u8], seed: u64, sender: &AccountId32, nonce: u64) -> StdRng {
+    StdRng::from_seed((domain, seed, sender, nonce).using_encoded(sp_core_hashing::blake2_256))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::*;
+
+    fn accounts(n: u8) -> Vec<AccountId32> {
+        (0..n).map(|i| AccountId32([i; 32])).collect()
+    }
+
+    #[test]
+    fn round_robin_walks_all_receivers_per_sender() {
+        let senders = accounts(4);
+        let destinations = DestinationConfig::RoundRobin
+            .build(senders.clone(), accounts(3))
+            .unwrap();
+        for (sender_index, sender) in senders.iter().enumerate() {
+            let picked: BTreeSet<_> = (0..3)
+                .map(|tx| destinations.pick(sender_index, tx, sender, tx as u64))
+                .collect();
+            assert_eq!(picked.len(), 3);
+        }
+    }
+
+    #[test]
+    fn sender_to_sender_skips_the_sender() {
+        let senders = accounts(3);
+        let destinations = DestinationConfig::SenderToSender { seed: 7 }
+            .build(senders.clone(), vec![])
+            .unwrap();
+        for (sender_index, sender) in senders.iter().enumerate() {
+            for nonce in 0..50 {
+                assert_ne!(destinations.pick(sender_index, 0, sender, nonce), sender);
+            }
+        }
+        assert!(DestinationConfig::SenderToSender { seed: 7 }
+            .build(accounts(1), vec![])
+            .is_err());
+    }
+
+    #[test]
+    fn zipf_ranks_stay_in_the_pool() {
+        let receivers = accounts(5);
+        let destinations = DestinationConfig::Zipf {
+            exponent: 0.5,
+            seed: 1,
+        }
+        .build(accounts(1), receivers.clone())
+        .unwrap();
+        let sender = AccountId32([9; 32]);
+        for nonce in 0..200 {
+            assert!(receivers.contains(destinations.pick(0, 0, &sender, nonce)));
+        }
+    }
+}
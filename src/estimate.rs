@@ -0,0 +1,116 @@
+/// What `TransactionPaymentApi_query_info` says about one sample tx of a call kind.
+#[derive(Debug, Clone)]
+pub struct CallEstimate {
+    pub partial_fee: u128,
+    pub ref_time: u64,
+    pub proof_size: u64,
+    // encoded length of the whole signed extrinsic
+    pub length: u64,
+}
+
+/// Per block capacity of normal dispatches.
+#[derive(Debug, Clone)]
+pub struct BlockLimits {
+    pub ref_time: u64,
+    pub proof_size: u64,
+    pub length: u64,
+    // weight frame_system charges every extrinsic on top of the call weight
+    pub base_extrinsic_ref_time: u64,
+    pub base_extrinsic_proof_size: u64,
+    // two timestamp minimum periods, the slot duration of aura style chains, until it is
+    // measured
+    pub block_time_ms: u64,
+}
+
+pub const RESOURCES: [&str; 3] = ["ref_time", "proof_size", "length"];
+
+impl BlockLimits {
+    /// Limits in the order of `RESOURCES`.
+    pub fn resources(&self) -> [u64; 3] {
+        [self.ref_time, self.proof_size, self.length]
+    }
+}
+
+/// Highest TPS the block limits allow for a workload whose calls are drawn with the given
+/// weights, and the resource that sets it.
+pub fn ceiling_tps(limits: &BlockLimits, calls: &[(u32, CallEstimate)]) -> (f64, &'static str) {
+    let total_weight: u32 = calls.iter().map(|(weight, _)| weight).sum();
+    if total_weight == 0 || limits.block_time_ms == 0 {
+        return (0.0, RESOURCES[0]);
+    }
+    // expected usage of one tx, averaged over the profile
+    let mut usage = [0f64; 3];
+    for (weight, call) in calls {
+        let share = f64::from(*weight) / f64::from(total_weight);
+        usage[0] += share * (call.ref_time + limits.base_extrinsic_ref_time) as f64;
+        usage[1] += share * (call.proof_size + limits.base_extrinsic_proof_size) as f64;
+        usage[2] += share * call.length as f64;
+    }
+    let (txs_per_block, bound) = limits
+        .resources()
+        .iter()
+        .zip(usage)
+        .zip(RESOURCES)
+        .filter(|((_, used), _)| *used > 0.0)
+        .map(|((limit, used), name)| ((*limit as f64 / used).floor(), name))
+        .min_by(|a, b| a.0.total_cmp(&b.0))
+        .unwrap_or((0.0, RESOURCES[0]));
+    (txs_per_block * 1000.0 / limits.block_time_ms as f64, bound)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits() -> BlockLimits {
+        BlockLimits {
+            ref_time: 1000,
+            proof_size: 10_000,
+            length: 100_000,
+            base_extrinsic_ref_time: 100,
+            base_extrinsic_proof_size: 0,
+            block_time_ms: 2000,
+        }
+    }
+
+    fn call(ref_time: u64, length: u64) -> CallEstimate {
+        CallEstimate {
+            partial_fee: 0,
+            ref_time,
+            proof_size: 0,
+            length,
+        }
+    }
+
+    #[test]
+    fn weighted_mix_averages_the_calls() {
+        // 0.75 * 200 + 0.25 * 600 = 300 ref time per tx, 3 txs per 2s block
+        let calls = [(3, call(100, 10)), (1, call(500, 10))];
+        assert_eq!(ceiling_tps(&limits(), &calls), (1.5, "ref_time"));
+    }
+
+    #[test]
+    fn the_tightest_resource_bounds() {
+        // 10 txs by ref time, 2 by length
+        let calls = [(1, call(0, 50_000))];
+        assert_eq!(ceiling_tps(&limits(), &calls), (1.0, "length"));
+    }
+
+    #[test]
+    fn base_extrinsic_weight_is_added() {
+        // 1000 / (400 + 100) = 2 txs per block, 2.5 without the base weight
+        let calls = [(1, call(400, 10))];
+        assert_eq!(ceiling_tps(&limits(), &calls), (1.0, "ref_time"));
+    }
+
+    #[test]
+    fn no_block_time_or_calls_gives_zero() {
+        let mut limits = limits();
+        assert_eq!(ceiling_tps(&limits, &[]), (0.0, "ref_time"));
+        limits.block_time_ms = 0;
+        assert_eq!(
+            ceiling_tps(&limits, &[(1, call(100, 10))]),
+            (0.0, "ref_time")
+        );
+    }
+}
@@ -0,0 +1,104 @@
+use std::str::FromStr;
+
+use anyhow::Result;
+use serde::Deserialize;
+use subxt_signer::SecretUri;
+
+use crate::account::{AccountKeypair, KeyScheme};
+
+// transfers per utility.batch_all, lowered further when a batch would not fit a block
+const DEFAULT_CHUNK_SIZE: u32 = 200;
+
+// above this many accounts the faucet funds intermediate accounts, which then fund the
+// bench accounts in parallel
+const DEFAULT_FAN_OUT_ABOVE: u32 = 1000;
+
+// accounts holding at least this share of the target (in percent) are not funded again
+const DEFAULT_TOP_UP_BELOW_PERCENT: u32 = 50;
+
+/// The `[funding]` table of the config file.
+#[derive(Debug, Clone, Deserialize)]
+pub struct FundingConfig {
+    #[serde(flatten)]
+    pub mode: FundingMode,
+    #[serde(default = "default_top_up_below_percent")]
+    pub top_up_below_percent: u32,
+}
+
+impl Default for FundingConfig {
+    fn default() -> Self {
+        Self {
+            mode: FundingMode::default(),
+            top_up_below_percent: DEFAULT_TOP_UP_BELOW_PERCENT,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum FundingMode {
+    /// `sudo(balances.force_set_balance)` signed by the sudo key.
+    #[default]
+    Sudo,
+    /// `utility.batch_all` of `balances.transfer_keep_alive` from a funded account, for
+    /// chains without sudo.
+    Faucet {
+        // secret uri of the faucet, e.g. a mnemonic or "//Alice"
+        seed: String,
+        #[serde(default)]
+        scheme: KeyScheme,
+        #[serde(default = "default_chunk_size")]
+        chunk_size: u32,
+        #[serde(default = "default_fan_out_above")]
+        fan_out_above: u32,
+    },
+}
+
+fn default_top_up_below_percent() -> u32 {
+    DEFAULT_TOP_UP_BELOW_PERCENT
+}
+
+fn default_chunk_size() -> u32 {
+    DEFAULT_CHUNK_SIZE
+}
+
+fn default_fan_out_above() -> u32 {
+    DEFAULT_FAN_OUT_ABOVE
+}
+
+/// Who pays for the bench accounts, and when an account counts as funded already.
+pub struct Funder {
+    pub payer: Payer,
+    pub top_up_below_percent: u32,
+}
+
+pub enum Payer {
+    Sudo(AccountKeypair),
+    Faucet {
+        faucet: AccountKeypair,
+        chunk_size: u32,
+        fan_out_above: u32,
+    },
+}
+
+impl FundingConfig {
+    pub fn build(&self, sudo: &AccountKeypair) -> Result<Funder> {
+        let payer = match &self.mode {
+            FundingMode::Sudo => Payer::Sudo(sudo.clone()),
+            FundingMode::Faucet {
+                seed,
+                scheme,
+                chunk_size,
+                fan_out_above,
+            } => Payer::Faucet {
+                faucet: AccountKeypair::from_uri(*scheme, &SecretUri::from_str(seed)?)?,
+                chunk_size: (*chunk_size).max(1),
+                fan_out_above: *fan_out_above,
+            },
+        };
+        Ok(Funder {
+            payer,
+            top_up_below_percent: self.top_up_below_percent,
+        })
+    }
+}
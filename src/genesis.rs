@@ -0,0 +1,193 @@
+use std::{collections::BTreeSet, fs};
+
+use anyhow::{anyhow, Result};
+use log::info;
+use serde_json::{json, Value};
+use subxt::utils::AccountId32;
+
+use crate::validator::ValidatorKeys;
+
+/// Write `base` to `out` with the given validators as the aura / grandpa / hotstuff
+/// authorities, whichever the runtime has, and `accounts` holding `balance` from genesis.
+/// `base` has to be a plain spec (`build-spec` without `--raw`).
+pub fn patch_spec(
+    base: &str,
+    out: &str,
+    validators: &[ValidatorKeys],
+    accounts: &[AccountId32],
+    balance: u64,
+) -> Result<()> {
+    let mut spec: Value = serde_json::from_str(&fs::read_to_string(base)?)?;
+    let runtime = spec
+        .pointer_mut("/genesis/runtime")
+        .and_then(Value::as_object_mut)
+        .ok_or(anyhow!(
+            "{} has no genesis.runtime, a raw chain spec can not be patched",
+            base
+        ))?;
+
+    let sr25519 = validators
+        .iter()
+        .map(|v| v.sr25519.account_id().to_string())
+        .collect::<Vec<_>>();
+    let mut patched = Vec::new();
+    for pallet in ["aura", "hotstuff"] {
+        if let Some(genesis) = runtime.get_mut(pallet) {
+            genesis["authorities"] = json!(sr25519);
+            patched.push(pallet);
+        }
+    }
+    if let Some(grandpa) = runtime.get_mut("grandpa") {
+        grandpa["authorities"] = validators
+            .iter()
+            .map(|v| json!([v.ed25519.account_id().to_string(), 1]))
+            .collect();
+        patched.push("grandpa");
+    }
+    if patched.is_empty() {
+        return Err(anyhow!("{} has no aura, grandpa or hotstuff genesis", base));
+    }
+
+    let balances = runtime
+        .get_mut("balances")
+        .and_then(|b| b.get_mut("balances"))
+        .and_then(Value::as_array_mut)
+        .ok_or(anyhow!("{} has no balances genesis", base))?;
+    // accounts already in the genesis keep their balance, each account is added once
+    let mut present = balances
+        .iter()
+        .filter_map(|entry| entry.get(0).and_then(Value::as_str).map(str::to_string))
+        .collect::<BTreeSet<_>>();
+    let mut added = 0;
+    for account in accounts {
+        let address = account.to_string();
+        if present.insert(address.clone()) {
+            balances.push(json!([address, balance]));
+            added += 1;
+        }
+    }
+
+    fs::write(out, serde_json::to_string_pretty(&spec)?)?;
+    info!(
+        "wrote {}: {} validators as {} authorities, {} pre-funded bench accounts",
+        out,
+        validators.len(),
+        patched.join("/"),
+        added
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validator::ValidatorConfig;
+
+    // writes `spec` to a fresh file, returns it and the path of the patched spec
+    fn spec_file(name: &str, spec: Value) -> (String, String) {
+        let base = std::env::temp_dir().join(format!(
+            "substrate-benchmark-{}-{}.json",
+            name,
+            std::process::id()
+        ));
+        fs::write(&base, spec.to_string()).unwrap();
+        let out = base.with_extension("patched.json");
+        (
+            base.to_string_lossy().into_owned(),
+            out.to_string_lossy().into_owned(),
+        )
+    }
+
+    fn patch(name: &str, runtime: Value, accounts: &[AccountId32]) -> Result<Value> {
+        let (base, out) = spec_file(name, json!({ "genesis": { "runtime": runtime } }));
+        let validators = ValidatorConfig::default().derive(2).unwrap();
+        let res = patch_spec(&base, &out, &validators, accounts, 1000).and_then(|_| {
+            Ok(
+                serde_json::from_str::<Value>(&fs::read_to_string(&out)?)?["genesis"]["runtime"]
+                    .clone(),
+            )
+        });
+        let _ = fs::remove_file(base);
+        let _ = fs::remove_file(out);
+        res
+    }
+
+    fn balances(runtime: &Value) -> Vec<(String, u64)> {
+        runtime["balances"]["balances"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|e| (e[0].as_str().unwrap().to_string(), e[1].as_u64().unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn aura_grandpa_authorities_are_replaced() {
+        let validators = ValidatorConfig::default().derive(2).unwrap();
+        let old = AccountId32([1; 32]).to_string();
+        let new = AccountId32([2; 32]).to_string();
+        let runtime = patch(
+            "aura",
+            json!({
+                "aura": { "authorities": [old] },
+                "grandpa": { "authorities": [[old, 1]] },
+                "balances": { "balances": [[old, 5]] },
+            }),
+            &[
+                AccountId32([1; 32]),
+                AccountId32([2; 32]),
+                AccountId32([2; 32]),
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(
+            runtime["aura"]["authorities"],
+            json!(validators
+                .iter()
+                .map(|v| v.sr25519.account_id().to_string())
+                .collect::<Vec<_>>())
+        );
+        assert_eq!(
+            runtime["grandpa"]["authorities"],
+            json!(validators
+                .iter()
+                .map(|v| json!([v.ed25519.account_id().to_string(), 1]))
+                .collect::<Vec<_>>())
+        );
+        assert_eq!(balances(&runtime), [(old, 5), (new, 1000)]);
+    }
+
+    #[test]
+    fn hotstuff_authorities_are_replaced() {
+        let validators = ValidatorConfig::default().derive(2).unwrap();
+        let runtime = patch(
+            "hotstuff",
+            json!({
+                "hotstuff": { "authorities": [] },
+                "grandpa": { "authorities": [] },
+                "balances": { "balances": [] },
+            }),
+            &[AccountId32([3; 32])],
+        )
+        .unwrap();
+        assert_eq!(
+            runtime["hotstuff"]["authorities"][1],
+            json!(validators[1].sr25519.account_id().to_string())
+        );
+        assert!(runtime.get("aura").is_none());
+        assert_eq!(
+            balances(&runtime),
+            [(AccountId32([3; 32]).to_string(), 1000)]
+        );
+    }
+
+    #[test]
+    fn specs_without_consensus_or_runtime_fail() {
+        let no_consensus = json!({ "balances": { "balances": [] } });
+        assert!(patch("babe", no_consensus, &[]).is_err());
+        let (base, out) = spec_file("raw", json!({ "genesis": { "raw": {} } }));
+        assert!(patch_spec(&base, &out, &[], &[], 1000).is_err());
+        let _ = fs::remove_file(base);
+    }
+}
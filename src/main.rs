@@ -1,11 +1,19 @@
-use std::{env, sync::Arc};
-
-use anyhow::Result;
+use std::{
+    env,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use anyhow::{anyhow, Result};
 use config::*;
 use futures::lock::Mutex;
-use log::debug;
-use subxt::{OnlineClient, SubstrateConfig};
-use subxt_signer::sr25519::{dev, PublicKey};
+use log::{debug, error, warn};
+use subxt::{utils::AccountId32, OnlineClient, SubstrateConfig};
+use subxt_signer::SecretUri;
 
 #[cfg(feature = "substrate")]
 #[subxt::subxt(runtime_metadata_path = "metadata/substrate_metadata.scale")]
@@ -16,15 +24,39 @@ pub mod substrate {}
 pub mod substrate {}
 
 pub mod account;
+mod account_list;
 pub mod client;
+mod destination;
+mod estimate;
+mod funding;
+mod genesis;
 mod metrics;
-
-use account::generate_bench_key_pairs;
+mod network;
+mod read_bench;
+mod retry;
+mod topology;
+mod validator;
+mod watch;
+mod workload;
+
+use account::{AccountKeypair, KeyConfig, KeyScheme};
 use client::Client;
-use metrics::Metrics;
+use destination::DestinationConfig;
+use funding::FundingConfig;
+use metrics::{Metrics, RunStatus};
+use network::NetworkConfig;
+use read_bench::ReadBenchConfig;
+use retry::RetryPolicy;
+use topology::TopologyConfig;
+use validator::{Consensus, ValidatorConfig};
+use watch::Timeouts;
+use workload::WorkloadConfig;
 
 const TOKEN_UNIT: u128 = 1_000_000_000_000u128;
-const TRANSFER_AMOUNT: u128 = 1000;
+// every sender's balance when the fees can not be estimated
+const FALLBACK_FUNDING: u128 = TOKEN_UNIT * 10000000;
+// what the endowed accounts of the node template genesis hold
+const DEFAULT_GENESIS_BALANCE: u64 = 1 << 60;
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -40,55 +72,377 @@ async fn main() -> Result<()> {
     let account_num: u32 = settings.get("account_number")?;
     let transaction_num: u32 = settings.get("every_account_tx")?;
     let stat_tps = settings.get("stat_tps")?;
+    // receivers can outnumber the senders, they are never funded nor signing
+    let receiver_num: u32 = get_or(&settings, "receiver_number", account_num)?;
+    let destination = get_or(&settings, "destination", DestinationConfig::default())?;
+    let workload_config = get_or(&settings, "workload", WorkloadConfig::default())?;
+    workload_config.validate()?;
+    let key_config = get_or(&settings, "derivation", KeyConfig::default())?;
+    if key_config.uses_dev_phrase() {
+        warn!(
+            "derivation.seed is empty, the bench accounts derive from the public dev phrase \
+             and anyone can drain them. set a seed for public networks"
+        );
+    }
+    let network = get_or(&settings, "network", NetworkConfig::default())?;
+    let active_profile = network.active()?.map(|(_, profile)| profile);
+
+    // `substrate-benchmark export-accounts <file.json|file.csv> [--with-secrets]` writes
+    // the bench accounts, e.g. to pre-fund them in a genesis, without touching any node
+    if env::args().nth(1).as_deref() == Some("export-accounts") {
+        let path = env::args()
+            .nth(2)
+            .ok_or(anyhow!("export-accounts needs a .json or .csv path"))?;
+        let with_secrets = env::args().any(|arg| arg == "--with-secrets");
+        return account_list::export(
+            &path,
+            &key_config,
+            &[("sender", account_num), ("receiver", receiver_num)],
+            with_secrets,
+        );
+    }
+
+    // `substrate-benchmark topology <out.yml>` writes the docker-compose file of the
+    // `[topology]` table and prints the client_urls to bench it
+    if env::args().nth(1).as_deref() == Some("topology") {
+        let out = env::args()
+            .nth(2)
+            .ok_or(anyhow!("usage: topology <out.yml>"))?;
+        let topology: TopologyConfig = settings.get("topology")?;
+        let urls = topology.write(&out, active_profile)?;
+        println!("# network: {}", network.applied()?);
+        println!("client_urls = [");
+        for url in urls {
+            println!("    \"{}\",", url);
+        }
+        println!("]");
+        return Ok(());
+    }
+
+    // `substrate-benchmark network <compose.yml>` reshapes the running nodes of a compose
+    // file written by `topology` to the active [network] profile
+    if env::args().nth(1).as_deref() == Some("network") {
+        let compose_file = env::args()
+            .nth(2)
+            .ok_or(anyhow!("usage: network <compose.yml>"))?;
+        let topology: TopologyConfig = settings.get("topology")?;
+        topology.apply(&compose_file, active_profile)?;
+        println!("network: {}", network.applied()?);
+        return Ok(());
+    }
+
+    // `substrate-benchmark keys <validators> <out_dir> <aura|hotstuff> [chain_id]` writes
+    // the keystores of the validators `spec` puts into the genesis
+    if env::args().nth(1).as_deref() == Some("keys") {
+        let args = env::args().skip(2).collect::<Vec<_>>();
+        let (validators, out_dir, consensus) = match args.as_slice() {
+            [validators, out_dir, consensus, ..] => (validators, out_dir, consensus),
+            _ => {
+                return Err(anyhow!(
+                    "usage: keys <validators> <out_dir> <aura|hotstuff> [chain_id]"
+                ))
+            }
+        };
+        let chain_id = args.get(3).map_or("local_testnet", String::as_str);
+        let validator_config = get_or(&settings, "validators", ValidatorConfig::default())?;
+        return validator::write_keystores(
+            &validator_config.derive(validators.parse()?)?,
+            consensus.parse::<Consensus>()?,
+            out_dir,
+            chain_id,
+        );
+    }
+
+    // `substrate-benchmark spec <base.json> <validators> <out.json>` patches a plain chain
+    // spec with the validator keys and pre-funded senders
+    if env::args().nth(1).as_deref() == Some("spec") {
+        let args = env::args().skip(2).collect::<Vec<_>>();
+        let [base, validators, out] = args.as_slice() else {
+            return Err(anyhow!("usage: spec <base.json> <validators> <out.json>"));
+        };
+        let validator_config = get_or(&settings, "validators", ValidatorConfig::default())?;
+        let genesis_balance = get_or(&settings, "genesis_balance", DEFAULT_GENESIS_BALANCE)?;
+        return genesis::patch_spec(
+            base,
+            out,
+            &validator_config.derive(validators.parse()?)?,
+            &key_config.derive_account_ids("sender", account_num)?,
+            genesis_balance,
+        );
+    }
 
+    let default_retry = RetryPolicy::default();
+    let retry = RetryPolicy::new(
+        get_or(&settings, "retry_max_attempts", default_retry.max_attempts)?,
+        get_or(
+            &settings,
+            "retry_base_delay_ms",
+            default_retry.base_delay.as_millis() as u64,
+        )?,
+        get_or(
+            &settings,
+            "retry_max_delay_ms",
+            default_retry.max_delay.as_millis() as u64,
+        )?,
+        get_or(
+            &settings,
+            "reconnect_max_attempts",
+            default_retry.reconnect_max_attempts,
+        )?,
+    );
+
+    let default_timeouts = Timeouts::default();
+    let timeouts = Timeouts::new(
+        get_or(
+            &settings,
+            "inclusion_timeout_secs",
+            default_timeouts.inclusion.as_secs(),
+        )?,
+        get_or(
+            &settings,
+            "finality_timeout_secs",
+            default_timeouts.finality.as_secs(),
+        )?,
+    );
+    // 0 means no deadline
+    let run_deadline_secs: u64 = get_or(&settings, "run_deadline_secs", 0)?;
+    // how long in-flight txs may still finalize after SIGINT/SIGTERM
+    let shutdown_grace_secs: u64 = get_or(&settings, "shutdown_grace_secs", 30)?;
+
+    // watch every n-th tx of each sender for the lifecycle stats, 0 = head and tail only
+    let watch_every: usize = get_or(&settings, "watch_every", 0)?;
+
+    let stop = Arc::new(AtomicBool::new(false));
     let mut clients = Vec::new();
     let metric = Arc::new(Mutex::new(Metrics::default()));
+    metric.lock().await.set_network(network.describe()?);
     for u in client_urls {
-        let c = Client::new(&u, metric.clone()).await?;
+        let c = Client::new(
+            &u,
+            metric.clone(),
+            retry.clone(),
+            timeouts.clone(),
+            stop.clone(),
+            watch_every,
+        )
+        .await?;
         clients.push(c);
     }
 
     let main_client = clients.first().expect("get client");
 
-    let from = dev::alice();
-    let sender_key_pairs = generate_bench_key_pairs("sender", account_num)?;
-    let receiver_key_pairs = generate_bench_key_pairs("receiver", account_num)?;
+    let from = AccountKeypair::from_uri(KeyScheme::Sr25519, &SecretUri::from_str("//Alice")?)?;
+    let funder = get_or(&settings, "funding", FundingConfig::default())?.build(&from)?;
+    let sender_key_pairs = key_config.derive("sender", account_num)?;
+    let receiver_ids = key_config.derive_account_ids("receiver", receiver_num)?;
 
-    let sender_pks = sender_key_pairs
+    let sender_ids = sender_key_pairs
         .iter()
-        .map(|k| k.public_key())
-        .collect::<Vec<PublicKey>>();
+        .map(|k| k.account_id())
+        .collect::<Vec<_>>();
+    let destinations = destination.build(sender_ids.clone(), receiver_ids)?;
+
+    // `substrate-benchmark read-bench` queries the nodes instead of sending txs
+    if env::args().nth(1).as_deref() == Some("read-bench") {
+        let read_config = get_or(&settings, "read_bench", ReadBenchConfig::default())?;
+        let run = read_bench::run(&clients, &read_config, &sender_ids, stop.clone());
+        tokio::pin!(run);
+        tokio::select! {
+            res = &mut run => res?,
+            res = shutdown_signal() => {
+                res?;
+                warn!("interrupted, wait for in-flight reads");
+                stop.store(true, Ordering::SeqCst);
+                tokio::select! {
+                    res = &mut run => res?,
+                    res = shutdown_signal() => {
+                        res?;
+                        exit_on_signal()
+                    }
+                }
+            }
+        }
+        return Ok(());
+    }
+
+    // `substrate-benchmark reclaim` sweeps the bench accounts back to the collector
+    if env::args().nth(1).as_deref() == Some("reclaim") {
+        let collector = match settings.get::<String>("reclaim_collector") {
+            Ok(address) => AccountId32::from_str(&address)
+                .map_err(|e| anyhow!("invalid reclaim_collector {}: {:?}", address, e))?,
+            Err(_) => from.account_id(),
+        };
+        let accounts = sender_key_pairs
+            .iter()
+            .cloned()
+            .chain(key_config.derive("receiver", receiver_num)?)
+            .collect::<Vec<_>>();
+        return main_client.reclaim(&accounts, &collector).await;
+    }
 
     if stat_tps {
-        // first, charge balance by sudo.
-        main_client
-            .charge_balance_to_account(&from, &sender_pks, TOKEN_UNIT * 10000000)
-            .await?;
-
-        let mut transfer_task = Vec::new();
-
-        for i in 0..account_num {
-            let target_client_index = i as usize % clients.len();
-
-            transfer_task.push(clients[target_client_index].batch_balance_transfer(
-                format!("task_{}", i),
-                &sender_key_pairs[i as usize],
-                receiver_key_pairs[i as usize].public_key(),
-                transaction_num,
-                TRANSFER_AMOUNT,
-            ));
+        let run = async {
+            main_client.check_transfer_amounts(&workload_config).await?;
+            let workload = main_client
+                .prepare_workload(&workload_config, &from, &funder, &sender_key_pairs)
+                .await?;
+
+            let sample_sender = if workload.sudo() {
+                &from
+            } else {
+                &sender_key_pairs[0]
+            };
+            let sample_receiver = destinations.pick(0, 0, &sample_sender.account_id(), 0);
+            let funding = match main_client
+                .estimate_workload(workload.as_ref(), sample_sender, sample_receiver)
+                .await
+            {
+                Ok(estimates) => {
+                    main_client.funding_amount(workload.as_ref(), &estimates, transaction_num)?
+                }
+                Err(e) => {
+                    warn!(
+                        "fee and weight estimation failed: {}, fund with {}",
+                        e, FALLBACK_FUNDING
+                    );
+                    FALLBACK_FUNDING
+                }
+            };
+
+            main_client
+                .fund_accounts(&funder, &sender_ids, funding)
+                .await?;
+
+            main_client.mark_run_start().await?;
+
+            let mut transfer_task = Vec::new();
+
+            if workload.sudo() {
+                // nonces are per account, so the sudo account sends everything in one task
+                warn!(
+                    "workload {} needs the sudo key, sending {} txs from the sudo account only",
+                    workload.name(),
+                    account_num * transaction_num
+                );
+                transfer_task.push(main_client.batch_workload_transfer(
+                    "task_sudo".to_string(),
+                    workload.as_ref(),
+                    &from,
+                    0,
+                    &destinations,
+                    account_num * transaction_num,
+                ));
+            } else {
+                for i in 0..account_num {
+                    let target_client_index = i as usize % clients.len();
+
+                    transfer_task.push(clients[target_client_index].batch_workload_transfer(
+                        format!("task_{}", i),
+                        workload.as_ref(),
+                        &sender_key_pairs[i as usize],
+                        i as usize,
+                        &destinations,
+                        transaction_num,
+                    ));
+                }
+            }
+
+            for res in futures::future::join_all(transfer_task).await {
+                if let Err(e) = res {
+                    error!("transfer task failed: {}", e);
+                }
+            }
+            anyhow::Ok(())
+        };
+
+        let deadline = async {
+            if run_deadline_secs > 0 {
+                tokio::time::sleep(Duration::from_secs(run_deadline_secs)).await
+            } else {
+                futures::future::pending().await
+            }
+        };
+
+        tokio::pin!(run);
+        tokio::select! {
+            res = &mut run => res?,
+            _ = deadline => {
+                warn!("run deadline of {}s reached", run_deadline_secs);
+                metric.lock().await.set_status(RunStatus::TimedOut);
+            }
+            res = shutdown_signal() => {
+                res?;
+                warn!(
+                    "interrupted, stop sending and wait {}s for in-flight txs",
+                    shutdown_grace_secs
+                );
+                stop.store(true, Ordering::SeqCst);
+                let grace = Duration::from_secs(shutdown_grace_secs);
+                tokio::select! {
+                    res = tokio::time::timeout(grace, &mut run) => {
+                        if let Ok(Err(e)) = res {
+                            error!("run failed after interrupt: {}", e);
+                        }
+                    }
+                    res = shutdown_signal() => {
+                        res?;
+                        exit_on_signal()
+                    }
+                }
+                metric.lock().await.set_status(RunStatus::Interrupted);
+            }
         }
 
-        futures::future::join_all(transfer_task).await;
+        // the signal handlers stay installed, so a signal while the blocks are walked has to
+        // end the process itself
+        tokio::select! {
+            res = main_client.report() => res?,
+            res = shutdown_signal() => {
+                res?;
+                exit_on_signal()
+            }
+        }
+    }
+
+    // main_client.stat_finalize_speed().await?;
+
+    Ok(())
+}
 
-        main_client.report().await?;
+/// An optional setting: `default` when the key is missing, an error when it is malformed.
+fn get_or<'de, T: serde::Deserialize<'de>>(settings: &Config, key: &str, default: T) -> Result<T> {
+    match settings.get(key) {
+        Ok(value) => Ok(value),
+        Err(ConfigError::NotFound(_)) => Ok(default),
+        Err(e) => Err(e.into()),
     }
+}
+
+/// Resolves on the first SIGINT (Ctrl-C) or SIGTERM.
+async fn shutdown_signal() -> Result<()> {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
 
-   // main_client.stat_finalize_speed().await?;
+        let mut terminate = signal(SignalKind::terminate())?;
+        tokio::select! {
+            res = tokio::signal::ctrl_c() => res?,
+            _ = terminate.recv() => {}
+        }
+    }
+    #[cfg(not(unix))]
+    tokio::signal::ctrl_c().await?;
 
     Ok(())
 }
 
+/// The signal handlers replace the default exit, so a signal while in-flight work drains or
+/// the report is built has to end the process itself.
+fn exit_on_signal() -> ! {
+    error!("interrupted, exit without waiting or reporting");
+    std::process::exit(130)
+}
+
 // Only display in debug mode. Maybe only start this task in debug mode.
 pub async fn monitor_best_block(url: String) -> Result<()> {
     let api = OnlineClient::<SubstrateConfig>::from_url(url).await?;
@@ -1,17 +1,146 @@
-use subxt::config::substrate::H256;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
+
+use subxt::{config::substrate::H256, utils::AccountId32};
+
+use crate::{
+    estimate::CallEstimate,
+    watch::{Terminal, TxLifecycle},
+};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RunStatus {
+    #[default]
+    Completed,
+    // a tx wait or the run deadline fired, the report only covers what was observed
+    TimedOut,
+    // stopped by SIGINT/SIGTERM
+    Interrupted,
+}
+
+impl fmt::Display for RunStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RunStatus::Completed => write!(f, "completed"),
+            RunStatus::TimedOut => write!(f, "timed out"),
+            RunStatus::Interrupted => write!(f, "interrupted"),
+        }
+    }
+}
+
+// A window in which a node websocket was down.
+pub struct Outage {
+    pub url: String,
+    pub begin: u64,
+    pub end: u64,
+}
+
+// Aggregated lifecycles of all watched txs, transition times in ms since submission.
+#[derive(Default)]
+pub struct LifecycleStats {
+    pub terminal: BTreeMap<Terminal, u32>,
+    pub validated: Vec<u64>,
+    pub broadcasted: Vec<u64>,
+    pub in_best_block: Vec<u64>,
+    pub finalized: Vec<u64>,
+    pub retracted: u32,
+}
+
+// Submitted txs of one call kind, finalization times of the watched ones in ms.
+#[derive(Default)]
+pub struct KindStats {
+    pub submitted: u32,
+    // summed encoded length of the submitted extrinsics
+    pub bytes: u64,
+    pub finalized: Vec<u64>,
+}
+
+/// Value at percentile `p` (0-100) of an ascending sorted slice.
+pub fn percentile(sorted: &[u64], p: f64) -> u64 {
+    if sorted.is_empty() {
+        return 0;
+    }
+    let rank = (p / 100.0 * (sorted.len() - 1) as f64).round() as usize;
+    sorted[rank.min(sorted.len() - 1)]
+}
 
 #[derive(Default)]
 pub struct Metrics {
+    pub status: RunStatus,
+    pub workload: String,
+    // per call kind, a single entry unless the workload is mixed
+    pub kinds: BTreeMap<String, KindStats>,
+    pub destination: String,
+    // the [network] profile the nodes run under
+    pub network: String,
+    // accounts that received at least one tx, what the run adds to the state
+    pub receivers: BTreeSet<AccountId32>,
+    // latest finalized block before sending, used when the first tx never finalized
+    pub run_start_block: Option<H256>,
     pub begin_send: u64,
     pub finalize_end: u64,
     pub first_tx_begin_block: Option<H256>,
     pub last_tx_finalize_block: Option<H256>,
     pub total_tx: u32,
+    // submissions that were retried, counted per retry
+    pub retries: u32,
+    // submissions that still failed after all retries
+    pub failed_tx: u32,
+    pub outages: Vec<Outage>,
+    // watched txs that hit the inclusion or finality timeout
+    pub timed_out_tx: u32,
+    pub lifecycle: LifecycleStats,
+    // query_info of one sample per call kind, taken before the run
+    pub estimates: BTreeMap<String, CallEstimate>,
+    // highest tps the block limits allow and the resource that bounds it
+    pub ceiling_tps: Option<(f64, &'static str)>,
 }
 
 impl Metrics {
+    pub fn set_status(&mut self, status: RunStatus) {
+        self.status = status
+    }
+
+    pub fn set_workload(&mut self, workload: String) {
+        self.workload = workload
+    }
+
+    pub fn set_destination(&mut self, destination: String) {
+        self.destination = destination
+    }
+
+    pub fn set_network(&mut self, network: String) {
+        self.network = network
+    }
+
+    pub fn add_receivers(&mut self, receivers: BTreeSet<AccountId32>) {
+        self.receivers.extend(receivers)
+    }
+
+    pub fn add_kind_submitted(&mut self, kind: &str, num: u32, bytes: u64) {
+        let stats = self.kinds.entry(kind.to_string()).or_default();
+        stats.submitted += num;
+        stats.bytes += bytes
+    }
+
+    pub fn set_estimates(
+        &mut self,
+        estimates: BTreeMap<String, CallEstimate>,
+        ceiling_tps: f64,
+        bound: &'static str,
+    ) {
+        self.estimates = estimates;
+        self.ceiling_tps = Some((ceiling_tps, bound))
+    }
+
+    pub fn set_run_start_block(&mut self, start: H256) {
+        self.run_start_block = Some(start)
+    }
+
     pub fn set_begin_block(&mut self, begin: H256) {
-        if self.first_tx_begin_block == None {
+        if self.first_tx_begin_block.is_none() {
             self.first_tx_begin_block = Some(begin)
         }
     }
@@ -33,4 +162,79 @@ impl Metrics {
     pub fn add_tx_number(&mut self, num: u32) {
         self.total_tx += num
     }
+
+    pub fn add_retries(&mut self, retries: u32) {
+        self.retries += retries
+    }
+
+    pub fn add_failed_tx(&mut self, num: u32) {
+        self.failed_tx += num
+    }
+
+    pub fn add_outage(&mut self, url: &str, begin: u64, end: u64) {
+        self.outages.push(Outage {
+            url: url.to_string(),
+            begin,
+            end,
+        })
+    }
+
+    // a sampled tx only counts, the first and last tx decide whether the run is partial
+    pub fn add_timed_out_tx(&mut self, num: u32) {
+        self.timed_out_tx += num
+    }
+
+    pub fn record_lifecycle(&mut self, kind: &str, lifecycle: &TxLifecycle) {
+        if let Some(finalized) = lifecycle.finalized {
+            self.kinds
+                .entry(kind.to_string())
+                .or_default()
+                .finalized
+                .push(finalized.as_millis() as u64);
+        }
+        let stats = &mut self.lifecycle;
+        if let Some(terminal) = lifecycle.terminal {
+            *stats.terminal.entry(terminal).or_default() += 1;
+        }
+        for (at, samples) in [
+            (lifecycle.validated, &mut stats.validated),
+            (lifecycle.broadcasted, &mut stats.broadcasted),
+            (lifecycle.in_best_block, &mut stats.in_best_block),
+            (lifecycle.finalized, &mut stats.finalized),
+        ] {
+            if let Some(at) = at {
+                samples.push(at.as_millis() as u64);
+            }
+        }
+        stats.retracted += lifecycle.retracted;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percentile_of_empty_is_zero() {
+        assert_eq!(percentile(&[], 50.0), 0);
+        assert_eq!(percentile(&[], 100.0), 0);
+    }
+
+    #[test]
+    fn percentile_bounds() {
+        let sorted = [10, 20, 30, 40, 50];
+        assert_eq!(percentile(&sorted, 0.0), 10);
+        assert_eq!(percentile(&sorted, 50.0), 30);
+        assert_eq!(percentile(&sorted, 100.0), 50);
+        // out of range percentiles stay on the last sample
+        assert_eq!(percentile(&sorted, 150.0), 50);
+        assert_eq!(percentile(&[7], 95.0), 7);
+    }
+
+    #[test]
+    fn percentile_rounds_to_the_nearest_rank() {
+        let sorted = (1..=100).collect::<Vec<u64>>();
+        assert_eq!(percentile(&sorted, 95.0), 95);
+        assert_eq!(percentile(&sorted, 99.0), 99);
+    }
 }
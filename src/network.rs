@@ -0,0 +1,338 @@
+use std::{collections::BTreeMap, fmt};
+
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+
+/// The `[network]` table of the config file, named network condition profiles and the
+/// one the nodes run under.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct NetworkConfig {
+    // name of a profile, none for an unshaped network
+    #[serde(default)]
+    pub active: Option<String>,
+    // the config crate lower cases the profile names
+    #[serde(default)]
+    pub profiles: BTreeMap<String, NetworkProfile>,
+}
+
+impl NetworkConfig {
+    /// The active profile with its name, an error when it is not defined.
+    pub fn active(&self) -> Result<Option<(&str, &NetworkProfile)>> {
+        let Some(name) = &self.active else {
+            return Ok(None);
+        };
+        let profile = self.profiles.get(&name.to_lowercase()).ok_or(anyhow!(
+            "network profile {} is not in [network.profiles]",
+            name
+        ))?;
+        Ok(Some((name, profile)))
+    }
+
+    /// What the report tags the results with, the active profile. Without one the nodes
+    /// may run anywhere, so nothing is claimed about their conditions.
+    pub fn describe(&self) -> Result<String> {
+        Ok(match self.active()? {
+            Some((name, profile)) => format!("{} ({})", name, profile),
+            None => "no profile (conditions unknown)".to_string(),
+        })
+    }
+
+    /// The conditions `topology` and `network` leave their nodes in, unshaped without an
+    /// active profile.
+    pub fn applied(&self) -> Result<String> {
+        match self.active()? {
+            Some(_) => self.describe(),
+            None => Ok(format!("unshaped ({})", NetworkProfile::default())),
+        }
+    }
+}
+
+/// Egress conditions of a container, as one netem qdisc.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct Conditions {
+    #[serde(default)]
+    pub delay_ms: u64,
+    // +- around the delay
+    #[serde(default)]
+    pub jitter_ms: u64,
+    #[serde(default)]
+    pub loss_percent: f64,
+    // 0 = unlimited
+    #[serde(default)]
+    pub rate_mbit: u64,
+}
+
+impl Conditions {
+    pub fn delay(delay_ms: u64) -> Self {
+        Self {
+            delay_ms,
+            ..Default::default()
+        }
+    }
+
+    fn netem(&self) -> String {
+        let mut netem = format!("netem delay {}ms", self.delay_ms);
+        if self.jitter_ms > 0 {
+            netem += &format!(" {}ms", self.jitter_ms);
+        }
+        if self.loss_percent > 0.0 {
+            netem += &format!(" loss {}%", self.loss_percent);
+        }
+        netem
+    }
+
+    fn rate(&self) -> String {
+        match self.rate_mbit {
+            0 => "10gbit".to_string(),
+            rate => format!("{}mbit", rate),
+        }
+    }
+}
+
+impl fmt::Display for Conditions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "delay {}ms", self.delay_ms)?;
+        if self.jitter_ms > 0 {
+            write!(f, " +-{}ms", self.jitter_ms)?;
+        }
+        if self.loss_percent > 0.0 {
+            write!(f, ", loss {}%", self.loss_percent)?;
+        }
+        if self.rate_mbit > 0 {
+            write!(f, ", rate {}mbit", self.rate_mbit)?;
+        }
+        Ok(())
+    }
+}
+
+/// Conditions of one node (counted from 1), replacing the profile conditions.
+#[derive(Debug, Clone, Deserialize)]
+pub struct NodeRule {
+    pub node: u32,
+    #[serde(flatten)]
+    pub conditions: Conditions,
+}
+
+/// Conditions between two nodes in both directions, replacing the node conditions of
+/// both.
+#[derive(Debug, Clone, Deserialize)]
+pub struct PairRule {
+    pub a: u32,
+    pub b: u32,
+    #[serde(flatten)]
+    pub conditions: Conditions,
+}
+
+/// Conditions of every node plus the nodes and pairs that differ.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct NetworkProfile {
+    #[serde(flatten)]
+    pub conditions: Conditions,
+    #[serde(default)]
+    pub nodes: Vec<NodeRule>,
+    #[serde(default)]
+    pub pairs: Vec<PairRule>,
+}
+
+impl fmt::Display for NetworkProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.conditions)?;
+        for rule in &self.nodes {
+            write!(f, "; node {}: {}", rule.node, rule.conditions)?;
+        }
+        for pair in &self.pairs {
+            write!(f, "; {}-{}: {}", pair.a, pair.b, pair.conditions)?;
+        }
+        Ok(())
+    }
+}
+
+impl NetworkProfile {
+    /// Fail on a node or pair rule that is not about the `nodes` nodes.
+    pub fn check(&self, nodes: u32) -> Result<()> {
+        let known = |n: u32| n > 0 && n <= nodes;
+        if let Some(rule) = self.nodes.iter().find(|r| !known(r.node)) {
+            return Err(anyhow!(
+                "node rule of node {} is not about one of the {} nodes",
+                rule.node,
+                nodes
+            ));
+        }
+        match self
+            .pairs
+            .iter()
+            .find(|p| p.a == p.b || !known(p.a) || !known(p.b))
+        {
+            Some(pair) => Err(anyhow!(
+                "pair {} - {} is not between two of the {} nodes",
+                pair.a,
+                pair.b,
+                nodes
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Conditions of all traffic of `node` without a pair rule.
+    pub fn node_conditions(&self, node: u32) -> &Conditions {
+        self.nodes
+            .iter()
+            .rev()
+            .find(|r| r.node == node)
+            .map_or(&self.conditions, |r| &r.conditions)
+    }
+
+    /// The compose `command` of `node`. run_node.sh sets one netem delay for all traffic by
+    /// itself, anything else runs the tc commands before it.
+    pub fn start_command(&self, node: u32, address: impl Fn(u32) -> String) -> String {
+        let conditions = self.node_conditions(node);
+        if self.peers(node).is_empty() && *conditions == Conditions::delay(conditions.delay_ms) {
+            return format!("[\"/usr/bin/run_node.sh\", \"{}\"]", conditions.delay_ms);
+        }
+        format!(
+            "[\"sh\", \"-c\", \"{} && exec /usr/bin/run_node.sh 0\"]",
+            self.tc_commands(node, address).join(" && ")
+        )
+    }
+
+    // Conditions towards each peer of `node` with a pair rule.
+    fn peers(&self, node: u32) -> BTreeMap<u32, &Conditions> {
+        self.pairs
+            .iter()
+            .filter_map(|p| {
+                if p.a == node {
+                    Some((p.b, &p.conditions))
+                } else if p.b == node {
+                    Some((p.a, &p.conditions))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// The tc commands shaping the egress of `node` on eth0, `address` gives the ip of a
+    /// peer. An htb class per peer with a pair rule, class 1:1 takes all other traffic,
+    /// each with its netem qdisc.
+    pub fn tc_commands(&self, node: u32, address: impl Fn(u32) -> String) -> Vec<String> {
+        let mut tc = vec![
+            "tc qdisc del dev eth0 root 2>/dev/null; tc qdisc add dev eth0 root handle 1: htb default 1".to_string(),
+        ];
+        let classes = std::iter::once((None, self.node_conditions(node))).chain(
+            self.peers(node)
+                .into_iter()
+                .map(|(peer, conditions)| (Some(peer), conditions)),
+        );
+        for (class, (peer, conditions)) in (1u32..).zip(classes) {
+            tc.push(format!(
+                "tc class add dev eth0 parent 1: classid 1:{:x} htb rate {}",
+                class,
+                conditions.rate()
+            ));
+            tc.push(format!(
+                "tc qdisc add dev eth0 parent 1:{:x} handle {:x}: {}",
+                class,
+                class + 1,
+                conditions.netem()
+            ));
+            if let Some(peer) = peer {
+                tc.push(format!(
+                    "tc filter add dev eth0 parent 1: protocol ip prio 1 u32 match ip dst {}/32 flowid 1:{:x}",
+                    address(peer),
+                    class
+                ));
+            }
+        }
+        tc
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn address(node: u32) -> String {
+        format!("10.0.0.{}", node)
+    }
+
+    fn profile() -> NetworkProfile {
+        NetworkProfile {
+            conditions: Conditions {
+                delay_ms: 50,
+                jitter_ms: 10,
+                loss_percent: 0.5,
+                rate_mbit: 100,
+            },
+            nodes: vec![NodeRule {
+                node: 1,
+                conditions: Conditions::delay(0),
+            }],
+            pairs: vec![PairRule {
+                a: 2,
+                b: 3,
+                conditions: Conditions::delay(150),
+            }],
+        }
+    }
+
+    #[test]
+    fn describe_names_the_conditions() {
+        let mut config = NetworkConfig::default();
+        assert_eq!(
+            config.describe().unwrap(),
+            "no profile (conditions unknown)"
+        );
+        assert_eq!(config.applied().unwrap(), "unshaped (delay 0ms)");
+
+        config.profiles.insert("wan".to_string(), profile());
+        config.active = Some("WAN".to_string());
+        assert_eq!(
+            config.describe().unwrap(),
+            "WAN (delay 50ms +-10ms, loss 0.5%, rate 100mbit; node 1: delay 0ms; 2-3: delay 150ms)"
+        );
+        assert_eq!(config.applied().unwrap(), config.describe().unwrap());
+
+        config.active = Some("lan".to_string());
+        assert!(config.describe().is_err());
+    }
+
+    #[test]
+    fn plain_delays_use_run_node_sh() {
+        let profile = profile();
+        assert_eq!(
+            profile.start_command(1, address),
+            "[\"/usr/bin/run_node.sh\", \"0\"]"
+        );
+        assert_eq!(
+            NetworkProfile::default().start_command(4, address),
+            "[\"/usr/bin/run_node.sh\", \"0\"]"
+        );
+        assert!(profile
+            .start_command(4, address)
+            .ends_with("exec /usr/bin/run_node.sh 0\"]"));
+    }
+
+    #[test]
+    fn tc_commands_shape_pairs_in_their_own_class() {
+        let tc = profile().tc_commands(3, address);
+        assert_eq!(
+            tc[1..],
+            [
+                "tc class add dev eth0 parent 1: classid 1:1 htb rate 100mbit",
+                "tc qdisc add dev eth0 parent 1:1 handle 2: netem delay 50ms 10ms loss 0.5%",
+                "tc class add dev eth0 parent 1: classid 1:2 htb rate 10gbit",
+                "tc qdisc add dev eth0 parent 1:2 handle 3: netem delay 150ms",
+                "tc filter add dev eth0 parent 1: protocol ip prio 1 u32 match ip dst 10.0.0.2/32 flowid 1:2",
+            ]
+        );
+    }
+
+    #[test]
+    fn rules_must_name_known_nodes() {
+        assert!(profile().check(3).is_ok());
+        assert!(profile().check(2).is_err());
+        let mut profile = profile();
+        profile.pairs[0].b = 2;
+        assert!(profile.check(3).is_err());
+    }
+}
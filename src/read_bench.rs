@@ -0,0 +1,233 @@
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use anyhow::{anyhow, Result};
+use futures::{stream::FuturesUnordered, StreamExt};
+use log::{info, warn};
+use serde::Deserialize;
+use subxt::utils::AccountId32;
+use tokio::time::{interval, Instant, MissedTickBehavior};
+
+use crate::{
+    client::Client,
+    metrics::percentile,
+    retry::{classify, ErrorKind},
+};
+
+/// A read the read bench sends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReadQuery {
+    // state_getStorage of a bench account's System.Account
+    Storage,
+    // chain_getBlock of the best block
+    Block,
+    // state_getRuntimeVersion
+    RuntimeVersion,
+    // system_health
+    Health,
+}
+
+impl ReadQuery {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ReadQuery::Storage => "state_getStorage",
+            ReadQuery::Block => "chain_getBlock",
+            ReadQuery::RuntimeVersion => "state_getRuntimeVersion",
+            ReadQuery::Health => "system_health",
+        }
+    }
+}
+
+/// The `[read_bench]` table of the config file.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ReadBenchConfig {
+    // queries per second sent to every node
+    #[serde(default = "default_rate")]
+    pub rate: u64,
+    #[serde(default = "default_duration_secs")]
+    pub duration_secs: u64,
+    // queries in flight per node, ticks beyond it are skipped and reported
+    #[serde(default = "default_concurrency")]
+    pub concurrency: usize,
+    // sent in turn
+    #[serde(default = "default_queries")]
+    pub queries: Vec<ReadQuery>,
+}
+
+fn default_rate() -> u64 {
+    100
+}
+
+fn default_duration_secs() -> u64 {
+    30
+}
+
+fn default_concurrency() -> usize {
+    64
+}
+
+fn default_queries() -> Vec<ReadQuery> {
+    vec![
+        ReadQuery::Storage,
+        ReadQuery::Block,
+        ReadQuery::RuntimeVersion,
+        ReadQuery::Health,
+    ]
+}
+
+impl Default for ReadBenchConfig {
+    fn default() -> Self {
+        Self {
+            rate: default_rate(),
+            duration_secs: default_duration_secs(),
+            concurrency: default_concurrency(),
+            queries: default_queries(),
+        }
+    }
+}
+
+// Latencies in microseconds.
+#[derive(Default)]
+struct QueryStats {
+    latencies: Vec<u64>,
+    errors: u32,
+}
+
+#[derive(Default)]
+struct NodeStats {
+    queries: BTreeMap<ReadQuery, QueryStats>,
+    // ticks dropped because `concurrency` queries were still in flight
+    skipped: u32,
+    elapsed: Duration,
+}
+
+/// Read every node at the configured rate for the configured time, then print latency
+/// percentiles and throughput per node and query. Stops early once `stop` is set.
+pub async fn run(
+    clients: &[Client],
+    config: &ReadBenchConfig,
+    accounts: &[AccountId32],
+    stop: Arc<AtomicBool>,
+) -> Result<()> {
+    if config.rate == 0 || config.queries.is_empty() {
+        return Err(anyhow!("read_bench needs a rate and at least one query"));
+    }
+    let main_client = clients.first().expect("get client");
+    let storage_keys = main_client.account_storage_keys(accounts)?;
+    if storage_keys.is_empty() {
+        return Err(anyhow!("read_bench needs bench accounts to read"));
+    }
+
+    info!(
+        "read bench: {} queries/s per node for {}s over {} nodes",
+        config.rate,
+        config.duration_secs,
+        clients.len()
+    );
+    let nodes = futures::future::join_all(
+        clients
+            .iter()
+            .map(|client| bench_node(client, config, &storage_keys, &stop)),
+    )
+    .await;
+
+    println!("***** read benchmark report *****");
+    for (client, node) in clients.iter().zip(nodes) {
+        let secs = node.elapsed.as_secs_f64().max(f64::EPSILON);
+        let total: usize = node.queries.values().map(|q| q.latencies.len()).sum();
+        println!(
+            "node {}: {} queries in {:.1}s, {:.1} queries/s, skipped ticks: {}",
+            client.url(),
+            total,
+            secs,
+            total as f64 / secs,
+            node.skipped
+        );
+        for (query, stats) in node.queries {
+            let mut sorted = stats.latencies;
+            sorted.sort_unstable();
+            let ms = |micros: u64| micros as f64 / 1000.0;
+            println!(
+                "  {}: ok {}, errors {}, {:.1}/s, p50 {:.2}ms, p95 {:.2}ms, p99 {:.2}ms, max {:.2}ms",
+                query.name(),
+                sorted.len(),
+                stats.errors,
+                sorted.len() as f64 / secs,
+                ms(percentile(&sorted, 50.0)),
+                ms(percentile(&sorted, 95.0)),
+                ms(percentile(&sorted, 99.0)),
+                ms(sorted.last().copied().unwrap_or(0))
+            );
+        }
+    }
+
+    Ok(())
+}
+
+async fn bench_node(
+    client: &Client,
+    config: &ReadBenchConfig,
+    storage_keys: &[Vec<u8>],
+    stop: &AtomicBool,
+) -> NodeStats {
+    let mut stats = NodeStats::default();
+    let begin = Instant::now();
+    let deadline = begin + Duration::from_secs(config.duration_secs);
+    // open loop: queries go out on the tick, whether or not earlier ones came back
+    let mut ticker = interval(Duration::from_secs_f64(1.0 / config.rate as f64));
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    let mut in_flight = FuturesUnordered::new();
+    let mut sent = 0usize;
+    // advances on storage queries only, so they walk over every key
+    let mut storage_reads = 0usize;
+
+    loop {
+        let sending = Instant::now() < deadline && !stop.load(Ordering::SeqCst);
+        tokio::select! {
+            _ = ticker.tick(), if sending => {
+                if in_flight.len() >= config.concurrency {
+                    stats.skipped += 1;
+                    continue;
+                }
+                let query = config.queries[sent % config.queries.len()];
+                let key = &storage_keys[storage_reads % storage_keys.len()];
+                let generation = client.generation();
+                sent += 1;
+                if query == ReadQuery::Storage {
+                    storage_reads += 1;
+                }
+                in_flight.push(async move {
+                    let begin = Instant::now();
+                    let res = client.read_query(query, key).await;
+                    (query, generation, begin.elapsed(), res)
+                });
+            }
+            Some((query, generation, elapsed, res)) = in_flight.next(), if !in_flight.is_empty() => {
+                let entry = stats.queries.entry(query).or_default();
+                match res {
+                    Ok(()) => entry.latencies.push(elapsed.as_micros() as u64),
+                    Err(e) => {
+                        entry.errors += 1;
+                        if classify(&e) == ErrorKind::Disconnected {
+                            if let Err(e) = client.reconnect(generation).await {
+                                warn!("read bench on {} gave up: {}", client.url(), e);
+                                break;
+                            }
+                        }
+                    }
+                }
+            }
+            else => break,
+        }
+    }
+
+    stats.elapsed = begin.elapsed();
+    stats
+}
@@ -0,0 +1,202 @@
+use std::{future::Future, time::Duration};
+
+use log::debug;
+
+/// How a failed submission should be treated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// Pool full, priority too low, request timeout... worth another try.
+    Retryable,
+    /// The websocket is gone, the client must reconnect before anything else works.
+    Disconnected,
+    /// The node already has this exact transaction, so an earlier attempt made it.
+    AlreadyImported,
+    /// Bad signature, stale nonce, no funds... retrying will not help.
+    Fatal,
+}
+
+// Fragments of the transaction pool / jsonrpsee error messages.
+const RETRYABLE_MESSAGES: &[&str] = &[
+    "Immediately Dropped",
+    "Priority is too low",
+    "too low priority",
+    "temporarily banned",
+    "request timeout",
+    "Request timeout",
+];
+
+const DISCONNECTED_MESSAGES: &[&str] = &[
+    "Networking or low-level protocol error",
+    "connection reset",
+    "Connection reset",
+    "restart required",
+    "RestartNeeded",
+    "subscription dropped",
+    "background task closed",
+];
+
+const ALREADY_IMPORTED_MESSAGES: &[&str] = &["Already Imported", "already imported"];
+
+pub fn classify(err: &subxt::Error) -> ErrorKind {
+    match err {
+        subxt::Error::Io(_) => ErrorKind::Disconnected,
+        subxt::Error::Rpc(_) => {
+            let msg = err.to_string();
+            if ALREADY_IMPORTED_MESSAGES.iter().any(|m| msg.contains(m)) {
+                ErrorKind::AlreadyImported
+            } else if DISCONNECTED_MESSAGES.iter().any(|m| msg.contains(m)) {
+                ErrorKind::Disconnected
+            } else if RETRYABLE_MESSAGES.iter().any(|m| msg.contains(m)) {
+                ErrorKind::Retryable
+            } else {
+                ErrorKind::Fatal
+            }
+        }
+        _ => ErrorKind::Fatal,
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    // total attempts including the first one
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    // attempts to re-open a dropped websocket before the task gives up
+    pub reconnect_max_attempts: u32,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(5),
+            reconnect_max_attempts: 30,
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn new(
+        max_attempts: u32,
+        base_delay_ms: u64,
+        max_delay_ms: u64,
+        reconnect_max_attempts: u32,
+    ) -> Self {
+        Self {
+            max_attempts: max_attempts.max(1),
+            base_delay: Duration::from_millis(base_delay_ms),
+            max_delay: Duration::from_millis(max_delay_ms),
+            reconnect_max_attempts: reconnect_max_attempts.max(1),
+        }
+    }
+
+    /// Exponential backoff: base, 2*base, 4*base... capped at `max_delay`.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 1u32.checked_shl(attempt).unwrap_or(u32::MAX);
+        self.base_delay
+            .checked_mul(factor)
+            .unwrap_or(self.max_delay)
+            .min(self.max_delay)
+    }
+
+    /// Run `op` until it succeeds, fails with a non retryable error or runs out of attempts.
+    /// Disconnects are returned right away, the caller has to reconnect first.
+    /// Returns the last result together with the number of retries that were made.
+    pub async fn run<T, F, Fut>(&self, mut op: F) -> (Result<T, subxt::Error>, u32)
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, subxt::Error>>,
+    {
+        let mut retries = 0;
+        loop {
+            match op().await {
+                Ok(v) => return (Ok(v), retries),
+                Err(e)
+                    if retries + 1 < self.max_attempts && classify(&e) == ErrorKind::Retryable =>
+                {
+                    let delay = self.backoff(retries);
+                    debug!("retryable error {}, retry after {}ms", e, delay.as_millis());
+                    retries += 1;
+                    tokio::time::sleep(delay).await;
+                }
+                Err(e) => return (Err(e), retries),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use subxt::error::RpcError;
+
+    fn rejected(msg: &str) -> subxt::Error {
+        subxt::Error::Rpc(RpcError::request_rejected(msg))
+    }
+
+    #[test]
+    fn classify_pool_errors() {
+        assert_eq!(
+            classify(&rejected("1014: Priority is too low")),
+            ErrorKind::Retryable
+        );
+        assert_eq!(
+            classify(&rejected("1016: Immediately Dropped")),
+            ErrorKind::Retryable
+        );
+        assert_eq!(
+            classify(&rejected("1013: Transaction Already Imported")),
+            ErrorKind::AlreadyImported
+        );
+        assert_eq!(
+            classify(&rejected(
+                "1010: Invalid Transaction: Inability to pay some fees"
+            )),
+            ErrorKind::Fatal
+        );
+    }
+
+    #[test]
+    fn classify_disconnects() {
+        assert_eq!(
+            classify(&subxt::Error::Rpc(RpcError::SubscriptionDropped)),
+            ErrorKind::Disconnected
+        );
+        assert_eq!(
+            classify(&rejected("Networking or low-level protocol error")),
+            ErrorKind::Disconnected
+        );
+        let io = std::io::Error::new(std::io::ErrorKind::BrokenPipe, "broken pipe");
+        assert_eq!(classify(&subxt::Error::Io(io)), ErrorKind::Disconnected);
+    }
+
+    #[test]
+    fn classify_other_errors_as_fatal() {
+        assert_eq!(
+            classify(&subxt::Error::Other("request timeout".to_string())),
+            ErrorKind::Fatal
+        );
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap() {
+        let policy = RetryPolicy::new(5, 100, 1000, 1);
+        let delays = (0..5)
+            .map(|a| policy.backoff(a).as_millis())
+            .collect::<Vec<_>>();
+        assert_eq!(delays, [100, 200, 400, 800, 1000]);
+        // factor and product overflows end at the cap too
+        assert_eq!(policy.backoff(40), Duration::from_millis(1000));
+        let policy = RetryPolicy::new(5, u64::MAX, u64::MAX, 1);
+        assert_eq!(policy.backoff(31), Duration::from_millis(u64::MAX));
+    }
+
+    #[test]
+    fn new_keeps_at_least_one_attempt() {
+        let policy = RetryPolicy::new(0, 100, 1000, 0);
+        assert_eq!(policy.max_attempts, 1);
+        assert_eq!(policy.reconnect_max_attempts, 1);
+    }
+}
@@ -0,0 +1,159 @@
+use std::{borrow::Cow, fmt::Write, fs, process::Command};
+
+use anyhow::{anyhow, Result};
+use log::info;
+use serde::Deserialize;
+
+use crate::network::NetworkProfile;
+
+// nodes get static addresses in this /16, so tc filters can match a peer
+const SUBNET_PREFIX: &str = "172.28";
+// node i is 172.28.x.y with x.y = i + this offset, past the gateway
+const ADDRESS_OFFSET: u32 = 10;
+
+/// The `[topology]` table of the config file, a docker-compose network of nodes.
+#[derive(Debug, Clone, Deserialize)]
+pub struct TopologyConfig {
+    pub nodes: u32,
+    pub image: String,
+    // cpu limit of every container
+    #[serde(default = "default_cpus")]
+    pub cpus: f64,
+    // relative to the compose file
+    pub volume_dir: String,
+    pub spec_dir: String,
+    // e.g. ["HOTSTUFF_DURATION=1000"]
+    #[serde(default)]
+    pub environment: Vec<String>,
+    // node i publishes its rpc port as rpc_port + i - 1
+    #[serde(default = "default_rpc_port")]
+    pub rpc_port: u16,
+    #[serde(default = "default_p2p_port")]
+    pub p2p_port: u16,
+    #[serde(default = "default_prometheus_port")]
+    pub prometheus_port: u16,
+    // host of the generated client_urls
+    #[serde(default = "default_host")]
+    pub host: String,
+}
+
+fn default_cpus() -> f64 {
+    0.3
+}
+
+fn default_rpc_port() -> u16 {
+    9944
+}
+
+fn default_p2p_port() -> u16 {
+    30333
+}
+
+fn default_prometheus_port() -> u16 {
+    9615
+}
+
+fn default_host() -> String {
+    "127.0.0.1".to_string()
+}
+
+impl TopologyConfig {
+    /// Write the compose file to `out` and return the matching `client_urls`. The nodes
+    /// start under `network`, unshaped without one.
+    pub fn write(&self, out: &str, network: Option<&NetworkProfile>) -> Result<Vec<String>> {
+        self.check(network)?;
+        fs::write(out, self.compose(network)?)?;
+        info!("wrote {} nodes to {}", self.nodes, out);
+        Ok((0..self.nodes)
+            .map(|i| format!("ws://{}:{}", self.host, u32::from(self.rpc_port) + i))
+            .collect())
+    }
+
+    /// Reshape the running nodes of the compose file `compose_file` to `network`, clear
+    /// their shaping without one.
+    pub fn apply(&self, compose_file: &str, network: Option<&NetworkProfile>) -> Result<()> {
+        self.check(network)?;
+        for node in 1..=self.nodes {
+            let tc = profile(network).tc_commands(node, address);
+            let status = Command::new("docker")
+                .args(["compose", "-f", compose_file, "exec", "-T"])
+                .arg(format!("node{}", node))
+                .args(["sh", "-c", &tc.join(" && ")])
+                .status()?;
+            if !status.success() {
+                return Err(anyhow!("tc on node{} failed: {}", node, status));
+            }
+        }
+        info!("reshaped {} nodes of {}", self.nodes, compose_file);
+        Ok(())
+    }
+
+    fn check(&self, network: Option<&NetworkProfile>) -> Result<()> {
+        if self.nodes == 0 {
+            return Err(anyhow!("a topology needs at least one node"));
+        }
+        profile(network).check(self.nodes)
+    }
+
+    fn compose(&self, network: Option<&NetworkProfile>) -> Result<String> {
+        let mut yml = String::new();
+        writeln!(yml, "version: '3'")?;
+        writeln!(yml, "services:")?;
+        writeln!(yml, "  base_node: &base_node")?;
+        writeln!(yml, "    image: {}", self.image)?;
+        if !self.environment.is_empty() {
+            writeln!(yml, "    environment:")?;
+            for env in &self.environment {
+                writeln!(yml, "    - {}", env)?;
+            }
+        }
+        writeln!(yml, "    cap_add:")?;
+        writeln!(yml, "    - NET_ADMIN")?;
+        writeln!(yml, "    deploy:")?;
+        writeln!(yml, "      resources:")?;
+        writeln!(yml, "        limits:")?;
+        writeln!(yml, "          cpus: '{}'", self.cpus)?;
+        writeln!(yml, "    networks:")?;
+        writeln!(yml, "      - mynetwork")?;
+
+        for node in 1..=self.nodes {
+            writeln!(yml)?;
+            writeln!(yml, "  node{}:", node)?;
+            writeln!(yml, "    <<: *base_node")?;
+            let command = profile(network).start_command(node, address);
+            writeln!(yml, "    command: {}", command)?;
+            writeln!(yml, "    ports:")?;
+            if node == 1 {
+                for port in [self.p2p_port, self.prometheus_port] {
+                    writeln!(yml, "      - {}:{}", port, port)?;
+                }
+            }
+            let rpc = u32::from(self.rpc_port) + node - 1;
+            writeln!(yml, "      - {}:{}", rpc, self.rpc_port)?;
+            writeln!(yml, "    networks:")?;
+            writeln!(yml, "      mynetwork:")?;
+            writeln!(yml, "        ipv4_address: {}", address(node))?;
+            writeln!(yml, "    volumes:")?;
+            writeln!(yml, "      - {}/node_{}:/data", self.volume_dir, node)?;
+            writeln!(yml, "      - {}:/chain_spec", self.spec_dir)?;
+        }
+
+        writeln!(yml)?;
+        writeln!(yml, "networks:")?;
+        writeln!(yml, "  mynetwork:")?;
+        writeln!(yml, "    ipam:")?;
+        writeln!(yml, "      config:")?;
+        writeln!(yml, "        - subnet: {}.0.0/16", SUBNET_PREFIX)?;
+        Ok(yml)
+    }
+}
+
+// An unshaped network is the default profile, no delay and no rules.
+fn profile(network: Option<&NetworkProfile>) -> Cow<'_, NetworkProfile> {
+    network.map_or_else(|| Cow::Owned(NetworkProfile::default()), Cow::Borrowed)
+}
+
+fn address(node: u32) -> String {
+    let n = node + ADDRESS_OFFSET;
+    format!("{}.{}.{}", SUBNET_PREFIX, n / 256, n % 256)
+}
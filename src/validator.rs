@@ -0,0 +1,139 @@
+use std::{fs, path::Path, str::FromStr};
+
+use anyhow::{anyhow, Result};
+use log::info;
+use serde::Deserialize;
+use subxt_signer::SecretUri;
+
+use crate::{
+    account::{write_private, AccountKeypair, KeyScheme},
+    workload::encode_hex,
+};
+
+const DEFAULT_PATH: &str = "//validator-{index}";
+
+/// The `[validators]` table of the config file. Validator i (counted from 1, as the
+/// `node_i` volumes) uses the secret uri seed + path for all of its session keys.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ValidatorConfig {
+    // mnemonic or 0x prefixed hex seed, the well known dev phrase when empty
+    #[serde(default)]
+    pub seed: String,
+    // {index} is replaced
+    #[serde(default = "default_path")]
+    pub path: String,
+}
+
+fn default_path() -> String {
+    DEFAULT_PATH.to_string()
+}
+
+impl Default for ValidatorConfig {
+    fn default() -> Self {
+        Self {
+            seed: String::new(),
+            path: default_path(),
+        }
+    }
+}
+
+/// Session keys of one validator, as `key generate --scheme Sr25519` followed by
+/// `key inspect --scheme Ed25519` of the same phrase.
+pub struct ValidatorKeys {
+    pub uri: String,
+    // aura or hotstuff
+    pub sr25519: AccountKeypair,
+    // grandpa
+    pub ed25519: AccountKeypair,
+}
+
+impl ValidatorConfig {
+    pub fn derive(&self, num: u32) -> Result<Vec<ValidatorKeys>> {
+        (1..=num)
+            .map(|index| {
+                let uri = format!(
+                    "{}{}",
+                    self.seed,
+                    self.path.replace("{index}", &index.to_string())
+                );
+                let secret_uri = SecretUri::from_str(&uri)?;
+                Ok(ValidatorKeys {
+                    sr25519: AccountKeypair::from_uri(KeyScheme::Sr25519, &secret_uri)?,
+                    ed25519: AccountKeypair::from_uri(KeyScheme::Ed25519, &secret_uri)?,
+                    uri,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Block production of the network, it decides the key type of the sr25519 key.
+#[derive(Debug, Clone, Copy)]
+pub enum Consensus {
+    // aura + grandpa
+    Aura,
+    // hotstuff + grandpa
+    Hotstuff,
+}
+
+impl FromStr for Consensus {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "aura" => Ok(Consensus::Aura),
+            "hotstuff" => Ok(Consensus::Hotstuff),
+            _ => Err(anyhow!("unknown consensus {}, aura or hotstuff", s)),
+        }
+    }
+}
+
+impl ValidatorKeys {
+    /// Key type id and public key of every session key, as `key insert` stores them.
+    fn session_keys(&self, consensus: Consensus) -> [(&'static [u8; 4], Vec<u8>); 2] {
+        let block_key_type = match consensus {
+            Consensus::Aura => b"aura",
+            Consensus::Hotstuff => b"hots",
+        };
+        [
+            (block_key_type, self.sr25519.public_key()),
+            (b"gran", self.ed25519.public_key()),
+        ]
+    }
+}
+
+/// Write the keystore of validator i to `<out_dir>/node_i/chains/<chain_id>/keystore`,
+/// the layout of the `*_volume` directories mounted as the node base path. A keystore
+/// file is named by the hex key type and public key and holds the secret uri as a JSON
+/// string, as `key insert` writes it.
+pub fn write_keystores(
+    validators: &[ValidatorKeys],
+    consensus: Consensus,
+    out_dir: &str,
+    chain_id: &str,
+) -> Result<()> {
+    for (i, validator) in validators.iter().enumerate() {
+        let node = format!("node_{}", i + 1);
+        let dir = Path::new(out_dir)
+            .join(&node)
+            .join("chains")
+            .join(chain_id)
+            .join("keystore");
+        fs::create_dir_all(&dir)?;
+        for (key_type, public) in validator.session_keys(consensus) {
+            let name = format!("{}{}", encode_hex(key_type), encode_hex(&public));
+            write_private(
+                &dir.join(name),
+                serde_json::to_string(&validator.uri)?.as_bytes(),
+            )?;
+        }
+        println!(
+            "{} sr25519 {} ed25519 {}",
+            node,
+            validator.sr25519.account_id(),
+            validator.ed25519.account_id()
+        );
+    }
+    info!("wrote {} keystores to {}", validators.len(), out_dir);
+    Ok(())
+}
@@ -0,0 +1,141 @@
+use std::time::Duration;
+
+use subxt::{
+    client::OnlineClientT,
+    error::{RpcError, TransactionError},
+    tx::{TxInBlock, TxProgress, TxStatus},
+    Config,
+};
+use tokio::time::{timeout_at, Instant};
+
+#[derive(Debug, Clone)]
+pub struct Timeouts {
+    // from submission until the tx shows up in a best block
+    pub inclusion: Duration,
+    // from inclusion until the block is finalized
+    pub finality: Duration,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Self {
+            inclusion: Duration::from_secs(120),
+            finality: Duration::from_secs(120),
+        }
+    }
+}
+
+impl Timeouts {
+    pub fn new(inclusion_secs: u64, finality_secs: u64) -> Self {
+        Self {
+            inclusion: Duration::from_secs(inclusion_secs),
+            finality: Duration::from_secs(finality_secs),
+        }
+    }
+}
+
+/// How a watched tx ended. Node statuses map onto the subxt ones: `Ready`/`Future` are
+/// `Validated`, `Retracted` is `NoLongerInBestBlock`, `Usurped` is reported as `Invalid`
+/// and `FinalityTimeout` as `Dropped`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Terminal {
+    Finalized,
+    Invalid,
+    Dropped,
+    Error,
+    TimedOut,
+    // the subscription went away with the connection
+    Lost,
+}
+
+impl Terminal {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Terminal::Finalized => "finalized",
+            Terminal::Invalid => "invalid",
+            Terminal::Dropped => "dropped",
+            Terminal::Error => "error",
+            Terminal::TimedOut => "timed_out",
+            Terminal::Lost => "lost",
+        }
+    }
+}
+
+/// Time from submission until each status was first seen.
+#[derive(Debug, Default)]
+pub struct TxLifecycle {
+    pub validated: Option<Duration>,
+    pub broadcasted: Option<Duration>,
+    pub in_best_block: Option<Duration>,
+    pub finalized: Option<Duration>,
+    // how often the including block left the best chain
+    pub retracted: u32,
+    pub terminal: Option<Terminal>,
+}
+
+/// Follow a watched tx until it is finalized or reaches another terminal status, giving up
+/// once the inclusion or finality timeout passes. `Ok(None)` means the tx timed out.
+pub async fn track<T: Config, C: OnlineClientT<T>>(
+    mut process: TxProgress<T, C>,
+    timeouts: &Timeouts,
+    submitted_at: Instant,
+) -> (TxLifecycle, Result<Option<TxInBlock<T, C>>, subxt::Error>) {
+    let mut lifecycle = TxLifecycle::default();
+    let mut deadline = Instant::now() + timeouts.inclusion;
+    let res = loop {
+        let status = match timeout_at(deadline, process.next()).await {
+            Ok(Some(Ok(status))) => status,
+            Ok(Some(Err(e))) => break Err(e),
+            Ok(None) => break Err(RpcError::SubscriptionDropped.into()),
+            Err(_) => {
+                lifecycle.terminal = Some(Terminal::TimedOut);
+                break Ok(None);
+            }
+        };
+        let elapsed = submitted_at.elapsed();
+        match status {
+            TxStatus::Validated => {
+                lifecycle.validated.get_or_insert(elapsed);
+            }
+            TxStatus::Broadcasted { .. } => {
+                lifecycle.broadcasted.get_or_insert(elapsed);
+            }
+            TxStatus::NoLongerInBestBlock => lifecycle.retracted += 1,
+            TxStatus::InBestBlock(_) => {
+                if lifecycle.in_best_block.is_none() {
+                    lifecycle.in_best_block = Some(elapsed);
+                    deadline = Instant::now() + timeouts.finality;
+                }
+            }
+            TxStatus::InFinalizedBlock(s) => {
+                lifecycle.finalized = Some(elapsed);
+                lifecycle.terminal = Some(Terminal::Finalized);
+                break Ok(Some(s));
+            }
+            TxStatus::Error { message } => {
+                lifecycle.terminal = Some(Terminal::Error);
+                break Err(TransactionError::Error(message).into());
+            }
+            TxStatus::Invalid { message } => {
+                lifecycle.terminal = Some(Terminal::Invalid);
+                break Err(TransactionError::Invalid(message).into());
+            }
+            TxStatus::Dropped { message } => {
+                lifecycle.terminal = Some(Terminal::Dropped);
+                break Err(TransactionError::Dropped(message).into());
+            }
+        }
+    };
+    if lifecycle.terminal.is_none() {
+        lifecycle.terminal = Some(Terminal::Lost);
+    }
+    (lifecycle, res)
+}
+
+/// Like `TxProgress::wait_for_finalized`, but with the inclusion and finality timeouts.
+pub async fn wait_for_finalized<T: Config, C: OnlineClientT<T>>(
+    process: TxProgress<T, C>,
+    timeouts: &Timeouts,
+) -> Result<Option<TxInBlock<T, C>>, subxt::Error> {
+    track(process, timeouts, Instant::now()).await.1
+}
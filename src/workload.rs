@@ -0,0 +1,820 @@
+use anyhow::{anyhow, Result};
+use rand::{distributions::WeightedIndex, prelude::Distribution, Rng};
+use serde::Deserialize;
+use subxt::{
+    dynamic::Value,
+    ext::scale_value::stringify::{self, custom_parsers},
+    tx::{DynamicPayload, TxPayload},
+    utils::AccountId32,
+    Metadata,
+};
+
+use crate::{client::substrate, destination::tx_rng};
+
+// config values are at most 64 bit, amounts are widened to u128 when building calls
+const DEFAULT_TRANSFER_AMOUNT: u64 = 1000;
+
+// gas limit of every contract call, well above what the bundled contract needs
+const DEFAULT_GAS_REF_TIME: u64 = 5_000_000_000;
+const DEFAULT_GAS_PROOF_SIZE: u64 = 256 * 1024;
+
+// size of the values written by set_storage
+const DEFAULT_STORAGE_VALUE_SIZE: usize = 32;
+
+// keys written by set_storage start with this, away from every pallet prefix
+const STORAGE_KEY_PREFIX: &[u8] = b"bench:";
+
+// asset of the assets workloads and what every sender gets minted
+const DEFAULT_ASSET_ID: u32 = 1000;
+const DEFAULT_ASSET_MINT: u64 = 1_000_000_000_000_000;
+
+// a contract whose `deploy` and `call` do nothing, see contracts/noop.wat
+const NOOP_CONTRACT: &[u8] = include_bytes!("../contracts/noop.wasm");
+
+/// What a single benchmark transaction is being sent for.
+pub struct CallContext<'a> {
+    pub sender: &'a AccountId32,
+    pub receiver: &'a AccountId32,
+    pub nonce: u64,
+}
+
+/// A call of any workload, generated from metadata or built dynamically. Calls are not
+/// checked against the codegen hash (subxt does not export `ValidationDetails`), a
+/// mismatch shows up when the tx is encoded or submitted instead.
+pub struct WorkloadCall {
+    // the call kind the report groups by, the name of the workload that built it
+    kind: String,
+    call: Box<dyn TxPayload + Send + Sync>,
+}
+
+impl WorkloadCall {
+    pub fn new(kind: String, call: impl TxPayload + Send + Sync + 'static) -> Self {
+        Self {
+            kind,
+            call: Box::new(call),
+        }
+    }
+
+    pub fn kind(&self) -> &str {
+        &self.kind
+    }
+}
+
+impl TxPayload for WorkloadCall {
+    fn encode_call_data_to(
+        &self,
+        metadata: &Metadata,
+        out: &mut Vec<u8>,
+    ) -> Result<(), subxt::Error> {
+        self.call.encode_call_data_to(metadata, out)
+    }
+}
+
+/// Produces the call every benchmark transaction carries.
+pub trait Workload: Send + Sync {
+    fn name(&self) -> String;
+
+    fn call(&self, ctx: &CallContext) -> Result<WorkloadCall>;
+
+    /// Whether the calls need the sudo key, so the sudo account has to sign them.
+    fn sudo(&self) -> bool {
+        false
+    }
+
+    /// Native balance that leaves the sender with every call, on top of the fee.
+    fn native_spend(&self) -> u128 {
+        0
+    }
+
+    /// The weighted workloads a mixed workload draws from, `None` for a single one.
+    fn profile(&self) -> Option<Vec<(u32, &dyn Workload)>> {
+        None
+    }
+}
+
+/// The `[workload]` table of the config file.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum WorkloadConfig {
+    TransferAllowDeath {
+        #[serde(default = "default_amount")]
+        amount: u64,
+    },
+    TransferKeepAlive {
+        #[serde(default = "default_amount")]
+        amount: u64,
+    },
+    Remark {
+        payload_size: usize,
+    },
+    RemarkWithEvent {
+        payload_size: usize,
+    },
+    UtilityBatch {
+        batch_size: u32,
+        #[serde(default = "default_amount")]
+        amount: u64,
+    },
+    UtilityBatchAll {
+        batch_size: u32,
+        #[serde(default = "default_amount")]
+        amount: u64,
+    },
+    SetStorage {
+        keys_per_tx: u32,
+        #[serde(default = "default_value_size")]
+        value_size: usize,
+        #[serde(default)]
+        seed: u64,
+    },
+    Mixed {
+        #[serde(default)]
+        seed: u64,
+        profile: Vec<WeightedWorkload>,
+    },
+    AssetsTransfer {
+        #[serde(default = "default_asset_id")]
+        asset_id: u32,
+        #[serde(default = "default_amount")]
+        amount: u64,
+        #[serde(default = "default_asset_mint")]
+        mint: u64,
+    },
+    AssetsTransferKeepAlive {
+        #[serde(default = "default_asset_id")]
+        asset_id: u32,
+        #[serde(default = "default_amount")]
+        amount: u64,
+        #[serde(default = "default_asset_mint")]
+        mint: u64,
+    },
+    ContractsCall {
+        // a .wasm or .contract file, the bundled no-op contract when missing
+        #[serde(default)]
+        code_path: Option<String>,
+        // hex encoded input of the constructor and of every call (selector + args)
+        #[serde(default)]
+        constructor: String,
+        #[serde(default)]
+        input: String,
+        #[serde(default = "default_gas_ref_time")]
+        gas_ref_time: u64,
+        #[serde(default = "default_gas_proof_size")]
+        gas_proof_size: u64,
+        #[serde(default)]
+        storage_deposit_limit: Option<u64>,
+        #[serde(default)]
+        value: u64,
+    },
+    Dynamic {
+        pallet: String,
+        call: String,
+        #[serde(default)]
+        args: Vec<String>,
+        #[serde(default)]
+        seed: u64,
+        // native balance one call moves out of the sender, funded on top of the fees
+        #[serde(default)]
+        spend: u64,
+    },
+}
+
+/// One entry of a `[[workload.profile]]` list.
+#[derive(Debug, Clone, Deserialize)]
+pub struct WeightedWorkload {
+    pub weight: u32,
+    #[serde(flatten)]
+    pub workload: WorkloadConfig,
+}
+
+fn default_amount() -> u64 {
+    DEFAULT_TRANSFER_AMOUNT
+}
+
+fn default_value_size() -> usize {
+    DEFAULT_STORAGE_VALUE_SIZE
+}
+
+fn default_asset_id() -> u32 {
+    DEFAULT_ASSET_ID
+}
+
+fn default_asset_mint() -> u64 {
+    DEFAULT_ASSET_MINT
+}
+
+fn default_gas_ref_time() -> u64 {
+    DEFAULT_GAS_REF_TIME
+}
+
+fn default_gas_proof_size() -> u64 {
+    DEFAULT_GAS_PROOF_SIZE
+}
+
+impl Default for WorkloadConfig {
+    fn default() -> Self {
+        WorkloadConfig::TransferAllowDeath {
+            amount: DEFAULT_TRANSFER_AMOUNT,
+        }
+    }
+}
+
+impl WorkloadConfig {
+    /// Check what needs no chain: the profile weights and parts of a mix and the
+    /// arguments of dynamic calls, so a bad config fails before any funding.
+    pub fn validate(&self) -> Result<()> {
+        match self {
+            WorkloadConfig::Mixed { profile, .. } => {
+                WeightedIndex::new(profile.iter().map(|p| p.weight))
+                    .map_err(|e| anyhow!("invalid workload profile weights: {}", e))?;
+                for p in profile {
+                    if matches!(p.workload, WorkloadConfig::Mixed { .. }) {
+                        return Err(anyhow!("mixed workloads can not be nested"));
+                    }
+                    if matches!(p.workload, WorkloadConfig::SetStorage { .. }) {
+                        return Err(anyhow!(
+                            "set_storage needs the sudo key, it can not be mixed"
+                        ));
+                    }
+                    p.workload.validate()?;
+                }
+                Ok(())
+            }
+            WorkloadConfig::Dynamic {
+                pallet,
+                call,
+                args,
+                seed,
+                spend,
+            } => Dynamic::new(pallet, call, args, *seed, *spend).map(|_| ()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Amounts receivers get from the native transfers of the workload or its mixed parts,
+    /// known before anything is set up on chain.
+    pub fn native_transfers(&self) -> Vec<u128> {
+        match self {
+            WorkloadConfig::TransferAllowDeath { amount }
+            | WorkloadConfig::TransferKeepAlive { amount }
+            | WorkloadConfig::UtilityBatch { amount, .. }
+            | WorkloadConfig::UtilityBatchAll { amount, .. } => vec![(*amount).into()],
+            WorkloadConfig::Mixed { profile, .. } => profile
+                .iter()
+                .flat_map(|p| p.workload.native_transfers())
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    /// Asset and amount of the asset transfers of the workload or its mixed parts.
+    pub fn asset_transfers(&self) -> Vec<(u32, u128)> {
+        match self {
+            WorkloadConfig::AssetsTransfer {
+                asset_id, amount, ..
+            }
+            | WorkloadConfig::AssetsTransferKeepAlive {
+                asset_id, amount, ..
+            } => vec![(*asset_id, (*amount).into())],
+            WorkloadConfig::Mixed { profile, .. } => profile
+                .iter()
+                .flat_map(|p| p.workload.asset_transfers())
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    pub fn build(&self) -> Result<Box<dyn Workload>> {
+        self.validate()?;
+        let workload: Box<dyn Workload> = match *self {
+            WorkloadConfig::TransferAllowDeath { amount } => {
+                Box::new(Transfer::allow_death(amount.into()))
+            }
+            WorkloadConfig::TransferKeepAlive { amount } => {
+                Box::new(Transfer::keep_alive(amount.into()))
+            }
+            WorkloadConfig::Remark { payload_size } => Box::new(Remark::new(payload_size, false)),
+            WorkloadConfig::RemarkWithEvent { payload_size } => {
+                Box::new(Remark::new(payload_size, true))
+            }
+            WorkloadConfig::UtilityBatch { batch_size, amount } => Box::new(UtilityBatch {
+                batch_size,
+                amount: amount.into(),
+                all: false,
+            }),
+            WorkloadConfig::UtilityBatchAll { batch_size, amount } => Box::new(UtilityBatch {
+                batch_size,
+                amount: amount.into(),
+                all: true,
+            }),
+            WorkloadConfig::SetStorage {
+                keys_per_tx,
+                value_size,
+                seed,
+            } => Box::new(SetStorage {
+                keys_per_tx,
+                value_size,
+                seed,
+            }),
+            WorkloadConfig::Mixed { seed, ref profile } => {
+                let workloads = profile
+                    .iter()
+                    .map(|p| Ok((p.weight, p.workload.build()?)))
+                    .collect::<Result<Vec<_>>>()?;
+                Box::new(Mixed::new(seed, workloads)?)
+            }
+            WorkloadConfig::AssetsTransfer { .. }
+            | WorkloadConfig::AssetsTransferKeepAlive { .. } => {
+                return Err(anyhow!(
+                    "assets workloads need a minted asset, use Client::prepare_workload"
+                ))
+            }
+            WorkloadConfig::ContractsCall { .. } => {
+                return Err(anyhow!(
+                    "contracts_call needs a deployed contract, use Client::prepare_workload"
+                ))
+            }
+            WorkloadConfig::Dynamic {
+                ref pallet,
+                ref call,
+                ref args,
+                seed,
+                spend,
+            } => Box::new(Dynamic::new(pallet, call, args, seed, spend)?),
+        };
+        Ok(workload)
+    }
+}
+
+/// `balances.transfer_allow_death` / `balances.transfer_keep_alive` to the receiver.
+pub struct Transfer {
+    amount: u128,
+    keep_alive: bool,
+}
+
+impl Transfer {
+    pub fn allow_death(amount: u128) -> Self {
+        Self {
+            amount,
+            keep_alive: false,
+        }
+    }
+
+    pub fn keep_alive(amount: u128) -> Self {
+        Self {
+            amount,
+            keep_alive: true,
+        }
+    }
+}
+
+impl Workload for Transfer {
+    fn name(&self) -> String {
+        if self.keep_alive {
+            "transfer_keep_alive".to_string()
+        } else {
+            "transfer_allow_death".to_string()
+        }
+    }
+
+    fn call(&self, ctx: &CallContext) -> Result<WorkloadCall> {
+        let dest = ctx.receiver.clone().into();
+        let balances = substrate::tx().balances();
+        let call = if self.keep_alive {
+            WorkloadCall::new(self.name(), balances.transfer_keep_alive(dest, self.amount))
+        } else {
+            WorkloadCall::new(
+                self.name(),
+                balances.transfer_allow_death(dest, self.amount),
+            )
+        };
+        Ok(call)
+    }
+
+    fn native_spend(&self) -> u128 {
+        self.amount
+    }
+}
+
+/// `system.remark` / `system.remark_with_event` with a payload of `payload_size` bytes.
+pub struct Remark {
+    payload_size: usize,
+    with_event: bool,
+}
+
+impl Remark {
+    pub fn new(payload_size: usize, with_event: bool) -> Self {
+        Self {
+            payload_size,
+            with_event,
+        }
+    }
+}
+
+impl Workload for Remark {
+    fn name(&self) -> String {
+        if self.with_event {
+            format!("remark_with_event({}B)", self.payload_size)
+        } else {
+            format!("remark({}B)", self.payload_size)
+        }
+    }
+
+    fn call(&self, _ctx: &CallContext) -> Result<WorkloadCall> {
+        let payload = vec![0u8; self.payload_size];
+        let system = substrate::tx().system();
+        let call = if self.with_event {
+            WorkloadCall::new(self.name(), system.remark_with_event(payload))
+        } else {
+            WorkloadCall::new(self.name(), system.remark(payload))
+        };
+        Ok(call)
+    }
+}
+
+/// `utility.batch` / `utility.batch_all` of `batch_size` transfers to the receiver. The
+/// node template has no utility pallet, so this is built dynamically against the metadata
+/// of the live chain.
+pub struct UtilityBatch {
+    batch_size: u32,
+    amount: u128,
+    // batch_all reverts the whole batch when one call fails
+    all: bool,
+}
+
+impl Workload for UtilityBatch {
+    fn name(&self) -> String {
+        if self.all {
+            format!("utility_batch_all({})", self.batch_size)
+        } else {
+            format!("utility_batch({})", self.batch_size)
+        }
+    }
+
+    fn call(&self, ctx: &CallContext) -> Result<WorkloadCall> {
+        let calls = (0..self.batch_size)
+            .map(|_| dynamic_transfer(ctx.receiver, self.amount).into_value())
+            .collect::<Vec<_>>();
+        let call_name = if self.all { "batch_all" } else { "batch" };
+        let batch = subxt::dynamic::tx("Utility", call_name, vec![Value::unnamed_composite(calls)]);
+        Ok(WorkloadCall::new(self.name(), batch))
+    }
+
+    fn native_spend(&self) -> u128 {
+        self.amount * u128::from(self.batch_size)
+    }
+}
+
+/// `sudo(system.set_storage)` writing `keys_per_tx` random keys, to stress trie writes.
+/// Only the sudo account can send it.
+pub struct SetStorage {
+    keys_per_tx: u32,
+    value_size: usize,
+    seed: u64,
+}
+
+impl Workload for SetStorage {
+    fn name(&self) -> String {
+        format!("set_storage({}x{}B)", self.keys_per_tx, self.value_size)
+    }
+
+    fn call(&self, ctx: &CallContext) -> Result<WorkloadCall> {
+        type Call = substrate::runtime_types::node_template_runtime::RuntimeCall;
+        type SystemCall = substrate::runtime_types::frame_system::pallet::Call;
+        let mut rng = tx_rng(b"set_storage", self.seed, ctx.sender, ctx.nonce);
+        let items = (0..self.keys_per_tx)
+            .map(|_| {
+                let mut key = STORAGE_KEY_PREFIX.to_vec();
+                key.extend(rng.gen::<[u8; 32]>());
+                let mut value = vec![0u8; self.value_size];
+                rng.fill(&mut value[..]);
+                (key, value)
+            })
+            .collect();
+        let call = Call::System(SystemCall::set_storage { items });
+        Ok(WorkloadCall::new(
+            self.name(),
+            substrate::tx().sudo().sudo(call),
+        ))
+    }
+
+    fn sudo(&self) -> bool {
+        true
+    }
+}
+
+/// `assets.transfer` / `assets.transfer_keep_alive` of an asset created and minted by
+/// `Client::prepare_workload`. Built dynamically like the other non template pallets.
+pub struct AssetTransfer {
+    asset_id: u32,
+    amount: u128,
+    keep_alive: bool,
+}
+
+impl AssetTransfer {
+    pub fn new(asset_id: u32, amount: u128, keep_alive: bool) -> Self {
+        Self {
+            asset_id,
+            amount,
+            keep_alive,
+        }
+    }
+}
+
+impl Workload for AssetTransfer {
+    fn name(&self) -> String {
+        if self.keep_alive {
+            format!("assets_transfer_keep_alive({})", self.asset_id)
+        } else {
+            format!("assets_transfer({})", self.asset_id)
+        }
+    }
+
+    fn call(&self, ctx: &CallContext) -> Result<WorkloadCall> {
+        let call_name = if self.keep_alive {
+            "transfer_keep_alive"
+        } else {
+            "transfer"
+        };
+        let call = subxt::dynamic::tx(
+            "Assets",
+            call_name,
+            vec![
+                Value::u128(self.asset_id.into()),
+                Value::unnamed_variant("Id", [Value::from_bytes(ctx.receiver.0)]),
+                Value::u128(self.amount),
+            ],
+        );
+        Ok(WorkloadCall::new(self.name(), call))
+    }
+}
+
+/// `contracts.call` of a contract deployed by `Client::prepare_workload`. Chains built
+/// from the node template have no contracts pallet, so the call is dynamic.
+pub struct ContractCall {
+    contract: AccountId32,
+    input: Vec<u8>,
+    value: u128,
+    gas_ref_time: u64,
+    gas_proof_size: u64,
+    storage_deposit_limit: Option<u128>,
+}
+
+impl ContractCall {
+    pub fn new(
+        contract: AccountId32,
+        input: Vec<u8>,
+        value: u128,
+        gas_ref_time: u64,
+        gas_proof_size: u64,
+        storage_deposit_limit: Option<u128>,
+    ) -> Self {
+        Self {
+            contract,
+            input,
+            value,
+            gas_ref_time,
+            gas_proof_size,
+            storage_deposit_limit,
+        }
+    }
+}
+
+impl Workload for ContractCall {
+    fn name(&self) -> String {
+        "contracts_call".to_string()
+    }
+
+    fn call(&self, _ctx: &CallContext) -> Result<WorkloadCall> {
+        let call = subxt::dynamic::tx(
+            "Contracts",
+            "call",
+            vec![
+                Value::unnamed_variant("Id", [Value::from_bytes(self.contract.0)]),
+                Value::u128(self.value),
+                gas_limit(self.gas_ref_time, self.gas_proof_size),
+                optional_balance(self.storage_deposit_limit),
+                Value::from_bytes(&self.input),
+            ],
+        );
+        Ok(WorkloadCall::new(self.name(), call))
+    }
+
+    fn native_spend(&self) -> u128 {
+        self.value + self.storage_deposit_limit.unwrap_or(0)
+    }
+}
+
+/// A `Weight` argument.
+pub fn gas_limit(ref_time: u64, proof_size: u64) -> Value {
+    Value::named_composite([
+        ("ref_time", Value::u128(ref_time.into())),
+        ("proof_size", Value::u128(proof_size.into())),
+    ])
+}
+
+/// An `Option<Compact<Balance>>` argument.
+pub fn optional_balance(balance: Option<u128>) -> Value {
+    match balance {
+        Some(balance) => Value::unnamed_variant("Some", [Value::u128(balance)]),
+        None => Value::unnamed_variant("None", []),
+    }
+}
+
+/// Wasm code of a contract: a plain `.wasm` file, the `source.wasm` field of an ink!
+/// `.contract` bundle or the bundled no-op contract.
+pub fn load_contract_code(path: Option<&str>) -> Result<Vec<u8>> {
+    let Some(path) = path else {
+        return Ok(NOOP_CONTRACT.to_vec());
+    };
+    let content = std::fs::read(path)?;
+    if !path.ends_with(".contract") {
+        return Ok(content);
+    }
+    let bundle: serde_json::Value = serde_json::from_slice(&content)?;
+    let wasm = bundle["source"]["wasm"]
+        .as_str()
+        .ok_or(anyhow!("{} has no source.wasm", path))?;
+    decode_hex(wasm)
+}
+
+/// Lower case hex of the bytes, without `0x`.
+pub fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Bytes of a hex string, with or without `0x`.
+pub fn decode_hex(hex: &str) -> Result<Vec<u8>> {
+    let hex = hex.trim_start_matches("0x");
+    if !hex.len().is_multiple_of(2) || !hex.is_ascii() {
+        return Err(anyhow!("invalid hex string {}", hex));
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&hex[i..i + 2], 16)
+                .map_err(|e| anyhow!("invalid hex string {}: {}", hex, e))
+        })
+        .collect()
+}
+
+/// A weighted mix of other workloads. Every tx draws its workload from an RNG seeded with
+/// the profile seed, the sender and the nonce, so a rerun with the same accounts sends the
+/// same sequence of calls no matter how the senders are scheduled.
+pub struct Mixed {
+    seed: u64,
+    workloads: Vec<(u32, Box<dyn Workload>)>,
+    index: WeightedIndex<u32>,
+}
+
+impl Mixed {
+    pub fn new(seed: u64, workloads: Vec<(u32, Box<dyn Workload>)>) -> Result<Self> {
+        // the sudo account sends alone, it would take every other part with it
+        if let Some((_, w)) = workloads.iter().find(|(_, w)| w.sudo()) {
+            return Err(anyhow!(
+                "{} needs the sudo key, it can not be mixed",
+                w.name()
+            ));
+        }
+        let index = WeightedIndex::new(workloads.iter().map(|(weight, _)| *weight))
+            .map_err(|e| anyhow!("invalid workload profile weights: {}", e))?;
+        Ok(Self {
+            seed,
+            workloads,
+            index,
+        })
+    }
+}
+
+impl Workload for Mixed {
+    fn name(&self) -> String {
+        let total: u32 = self.workloads.iter().map(|(weight, _)| weight).sum();
+        let parts = self
+            .workloads
+            .iter()
+            .map(|(weight, w)| {
+                format!(
+                    "{:.0}% {}",
+                    f64::from(*weight) * 100.0 / f64::from(total),
+                    w.name()
+                )
+            })
+            .collect::<Vec<_>>();
+        format!("mixed({})", parts.join(", "))
+    }
+
+    fn call(&self, ctx: &CallContext) -> Result<WorkloadCall> {
+        let mut rng = tx_rng(b"mix", self.seed, ctx.sender, ctx.nonce);
+        self.workloads[self.index.sample(&mut rng)].1.call(ctx)
+    }
+
+    // sized for the worst draw
+    fn native_spend(&self) -> u128 {
+        self.workloads
+            .iter()
+            .map(|(_, w)| w.native_spend())
+            .max()
+            .unwrap_or(0)
+    }
+
+    fn profile(&self) -> Option<Vec<(u32, &dyn Workload)>> {
+        Some(
+            self.workloads
+                .iter()
+                .map(|(weight, w)| (*weight, w.as_ref()))
+                .collect(),
+        )
+    }
+}
+
+/// Any call of the live runtime, with the arguments written as SCALE values in the
+/// `scale-value` text syntax, e.g. `Id({receiver})` or `{ a: 1, b: (0, 1) }`. Before
+/// parsing, `{sender}` and `{receiver}` become ss58 addresses, `{nonce}` the tx nonce and
+/// `{random_u32}` a number from an RNG seeded like the mixed workload.
+pub struct Dynamic {
+    pallet: String,
+    call: String,
+    args: Vec<String>,
+    seed: u64,
+    // what the call spends of the native balance, the call itself is opaque
+    spend: u128,
+}
+
+impl Dynamic {
+    pub fn new(pallet: &str, call: &str, args: &[String], seed: u64, spend: u64) -> Result<Self> {
+        let dynamic = Self {
+            pallet: pallet.to_string(),
+            call: call.to_string(),
+            args: args.to_vec(),
+            seed,
+            spend: spend.into(),
+        };
+        // surface syntax errors at startup rather than on the first tx
+        let placeholder = AccountId32([0u8; 32]);
+        dynamic.values(&CallContext {
+            sender: &placeholder,
+            receiver: &placeholder,
+            nonce: 0,
+        })?;
+        Ok(dynamic)
+    }
+
+    fn values(&self, ctx: &CallContext) -> Result<Vec<Value>> {
+        let mut rng = tx_rng(b"dynamic", self.seed, ctx.sender, ctx.nonce);
+        self.args
+            .iter()
+            .map(|arg| {
+                let mut arg = arg
+                    .replace("{sender}", &ctx.sender.to_string())
+                    .replace("{receiver}", &ctx.receiver.to_string())
+                    .replace("{nonce}", &ctx.nonce.to_string());
+                while arg.contains("{random_u32}") {
+                    arg = arg.replacen("{random_u32}", &rng.gen::<u32>().to_string(), 1);
+                }
+                let (value, rest) = stringify::from_str_custom()
+                    .add_custom_parser(custom_parsers::parse_ss58)
+                    .parse(&arg);
+                let value = value.map_err(|e| {
+                    anyhow!("{}.{} argument `{}`: {}", self.pallet, self.call, arg, e)
+                })?;
+                if !rest.trim().is_empty() {
+                    return Err(anyhow!(
+                        "{}.{} argument `{}`: unexpected trailing `{}`",
+                        self.pallet,
+                        self.call,
+                        arg,
+                        rest
+                    ));
+                }
+                Ok(value)
+            })
+            .collect()
+    }
+}
+
+impl Workload for Dynamic {
+    fn name(&self) -> String {
+        format!("{}.{}", self.pallet, self.call)
+    }
+
+    fn call(&self, ctx: &CallContext) -> Result<WorkloadCall> {
+        let call = subxt::dynamic::tx(&self.pallet, &self.call, self.values(ctx)?);
+        Ok(WorkloadCall::new(self.name(), call))
+    }
+
+    fn native_spend(&self) -> u128 {
+        self.spend
+    }
+}
+
+/// A dynamic `balances.transfer_keep_alive`, for batching.
+pub fn dynamic_transfer(dest: &AccountId32, amount: u128) -> DynamicPayload {
+    subxt::dynamic::tx(
+        "Balances",
+        "transfer_keep_alive",
+        vec![
+            Value::unnamed_variant("Id", [Value::from_bytes(dest.0)]),
+            Value::u128(amount),
+        ],
+    )
+}